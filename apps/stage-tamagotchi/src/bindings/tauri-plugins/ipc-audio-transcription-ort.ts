@@ -0,0 +1,452 @@
+// @ts-nocheck
+
+// This file was generated by [tauri-specta](https://github.com/oscartbeaumont/tauri-specta). Do not edit this file manually.
+
+/** user-defined commands **/
+
+
+export const commands = {
+async loadOrtModelWhisper(modelType: string | null, modelDir: string | null, modelFiles: Partial<{ [key in string]: string }> | null, precision: ModelPrecision | null) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|load_ort_model_whisper", { modelType, modelDir, modelFiles, precision }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async unloadOrtModelWhisper() : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|unload_ort_model_whisper") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getLoadedWhisperModel() : Promise<Result<LoadedWhisperModel | null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|get_loaded_whisper_model") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async ipcAudioTranscription(chunk: number[], language: string | null, options: TranscriptionOptions | null, sampleRate: number | null, channels: number | null, onToken: TAURI_CHANNEL<StreamedToken> | null, request: RequestOptions | null) : Promise<Result<string, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|ipc_audio_transcription", { chunk, language, options, sampleRate, channels, onToken, request }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async ipcAudioTranscriptionWithTimestamps(chunk: number[], language: string | null, wordTimestamps: boolean | null, options: TranscriptionOptions | null, sampleRate: number | null, channels: number | null, onToken: TAURI_CHANNEL<StreamedToken> | null, request: RequestOptions | null) : Promise<Result<TranscriptionResult, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|ipc_audio_transcription_with_timestamps", { chunk, language, wordTimestamps, options, sampleRate, channels, onToken, request }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async ipcAudioTranscriptionBatch(chunks: number[][], language: string | null, wordTimestamps: boolean | null, options: TranscriptionOptions | null, sampleRate: number | null, channels: number | null, onToken: TAURI_CHANNEL<StreamedToken> | null, request: RequestOptions | null) : Promise<Result<TranscriptionResult[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|ipc_audio_transcription_batch", { chunks, language, wordTimestamps, options, sampleRate, channels, onToken, request }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async transcribeFile(path: string, language: string | null, wordTimestamps: boolean | null, options: TranscriptionOptions | null, onToken: TAURI_CHANNEL<StreamedToken> | null, request: RequestOptions | null) : Promise<Result<TranscriptionResult, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|transcribe_file", { path, language, wordTimestamps, options, onToken, request }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async detectLanguage(chunk: number[], request: RequestOptions | null) : Promise<Result<LanguageProbability[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|detect_language", { chunk, request }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async startTranscriptionStream(language: string | null, wordTimestamps: boolean | null, options: TranscriptionOptions | null, sampleRate: number | null, channels: number | null) : Promise<Result<number, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|start_transcription_stream", { language, wordTimestamps, options, sampleRate, channels }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Transcribes the next chunk of a stream. Pushes are cancelled by stopping their stream, only
+ * the priority of `request` is used.
+ */
+async pushTranscriptionStream(sessionId: number, chunk: number[], request: RequestOptions | null) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|push_transcription_stream", { sessionId, chunk, request }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async stopTranscriptionStream(sessionId: number) : Promise<Result<string, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|stop_transcription_stream", { sessionId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Cancels the queued or running requests with the given id. Returns whether there was any.
+ */
+async cancelTranscription(requestId: string) : Promise<Result<boolean, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|cancel_transcription", { requestId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getTranscriptionQueueStatus() : Promise<Result<WorkerStatus | null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|get_transcription_queue_status") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Sets the session options of the models loaded from now on, the loaded model keeps its own
+ * until it is loaded again.
+ */
+async setTranscriptionSessionOptions(options: SessionOptions) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|set_transcription_session_options", { options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getTranscriptionSessionOptions() : Promise<Result<SessionOptions, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|get_transcription_session_options") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * The execution providers ONNX Runtime can use on this machine.
+ */
+async listExecutionProviders() : Promise<Result<Provider[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-transcription-ort|list_execution_providers") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+}
+}
+
+/** user-defined events **/
+
+
+
+/** user-defined constants **/
+
+
+
+/** user-defined types **/
+
+/**
+ * How the memory arena of the GPU providers grows once it is full.
+ */
+export type ArenaGrowth = 
+/**
+ * Grows by larger and larger amounts, fewer allocations but more memory held.
+ */
+"next_power_of_two" | 
+/**
+ * Grows by what was requested.
+ */
+"same_as_requested"
+
+export type LanguageProbability = { language: string; probability: number }
+
+export type LoadedWhisperModel = { model_type: string; source: string; precision: ModelPrecision }
+
+/**
+ * Which variant of the ONNX export to load. Hugging Face exports (e.g. `onnx-community/*`) ship
+ * every variant next to each other, told apart by a suffix of the file name
+ * (`onnx/encoder_model_q4.onnx`).
+ * 
+ * The quantized variants are several times smaller and much faster on CPU, the half precision
+ * ones are meant for GPUs.
+ */
+export type ModelPrecision = 
+/**
+ * Full precision weights, `*.onnx`.
+ */
+"fp32" | 
+/**
+ * Half precision weights, inputs and outputs, `*_fp16.onnx`.
+ */
+"fp16" | 
+/**
+ * Dynamically quantized 8-bit weights, `*_quantized.onnx`.
+ */
+"quantized" | 
+/**
+ * Signed 8-bit weights, `*_int8.onnx`.
+ */
+"int8" | 
+/**
+ * Unsigned 8-bit weights, `*_uint8.onnx`.
+ */
+"uint8" | 
+/**
+ * 4-bit weights with full precision activations, `*_q4.onnx`.
+ */
+"q4" | 
+/**
+ * 4-bit weights with half precision activations, `*_q4f16.onnx`.
+ */
+"q4f16" | 
+/**
+ * 4-bit weights quantized with bitsandbytes, `*_bnb4.onnx`.
+ */
+"bnb4"
+
+export type OptimizationLevel = "disable" | "level1" | "level2" | "level3"
+
+/**
+ * How urgently a request should run. Queued requests run by priority, then in arrival order.
+ */
+export type Priority = 
+/**
+ * Backlog work, e.g. re-transcribing recordings.
+ */
+"background" | "normal" | 
+/**
+ * Live speech, someone is waiting for the result.
+ */
+"live"
+
+/**
+ * An execution provider ONNX Runtime can run the models on.
+ */
+export type Provider = "cuda" | "coreml" | "directml" | "cpu"
+
+/**
+ * Per-request options of the inference commands.
+ */
+export type RequestOptions = { 
+/**
+ * Id chosen by the caller to cancel the request with.
+ */
+id?: string | null; priority?: Priority | null }
+
+/**
+ * How ONNX Runtime sessions are created. Applies to the models loaded afterwards.
+ */
+export type SessionOptions = { 
+/**
+ * Providers to run the models on, in order of preference. Whatever they cannot run falls back
+ * to the CPU, even when it is not listed.
+ */
+providers?: Provider[]; 
+/**
+ * Threads running a single operator, ONNX Runtime picks one per core when unset. Cap it on
+ * shared machines so that inference does not starve the other processes.
+ */
+intra_threads?: number | null; 
+/**
+ * Threads running independent operators at once, only used with `parallel_execution`.
+ */
+inter_threads?: number | null; parallel_execution?: boolean; optimization_level?: OptimizationLevel; 
+/**
+ * Keeps the memory of the CPU provider for later runs instead of freeing it.
+ */
+memory_arena?: boolean; 
+/**
+ * Plans the memory of later runs from the first one, helps when the input shapes are fixed.
+ */
+memory_pattern?: boolean; arena_extend_strategy?: ArenaGrowth }
+
+/**
+ * A token sent to the [`TokenListener`] while a window is being decoded.
+ */
+export type StreamedToken = { 
+/**
+ * Index of the chunk in [`WhisperPipeline::transcribe_batch`], `0` otherwise.
+ */
+chunk: number; 
+/**
+ * Index of the 30 second window within the chunk.
+ */
+window: number; token: number; 
+/**
+ * Text added by the token, empty for special and timestamp tokens, and for bytes of a
+ * character that is not complete yet.
+ */
+text: string; 
+/**
+ * Temperature the window is decoded at. A window decoded again at a higher temperature starts
+ * over, the text streamed for it before should be dropped.
+ */
+temperature: number }
+
+/**
+ * Text that was just committed, with the time span it covers in seconds from the start of the stream.
+ */
+export type StreamingFinal = { session_id: number; text: string; start: number; end: number }
+
+/**
+ * The transcript of a stream after a decode: the committed text, which will not change anymore,
+ * followed by the tentative text that may still be revised by the next decode.
+ */
+export type StreamingPartial = { session_id: number; committed: string; tentative: string }
+
+/**
+ * What the decoder is asked to produce.
+ */
+export type Task = 
+/**
+ * Text in the spoken language.
+ */
+"transcribe" | 
+/**
+ * English text, whatever the spoken language.
+ */
+"translate"
+
+/**
+ * Generation settings that can be set from the frontend, missing fields keep the values given
+ * by the command arguments or the defaults of [`GenerationConfig`].
+ */
+export type TranscriptionOptions = { 
+/**
+ * Language code or name, or [`AUTO_LANGUAGE`] to detect it.
+ */
+language?: string | null; task?: Task | null; return_timestamps?: boolean | null; return_word_timestamps?: boolean | null; max_new_tokens?: number | null; beam_size?: number | null; patience?: number | null; length_penalty?: number | null; temperatures?: number[] | null; compression_ratio_threshold?: number | null; logprob_threshold?: number | null; initial_prompt?: string | null; condition_on_previous_text?: boolean | null; previous_text?: string | null; suppress_tokens?: number[] | null; suppress_blank?: boolean | null; max_initial_timestamp?: number | null; no_speech_threshold?: number | null; repetition_threshold?: number | null; batch_size?: number | null }
+
+export type TranscriptionResult = { text: string; 
+/**
+ * Language code the audio was transcribed in, either requested or detected.
+ */
+language: string | null; segments: TranscriptionSegment[]; 
+/**
+ * At least one window was judged to be silence, its output was left out of the text.
+ */
+no_speech: boolean; 
+/**
+ * At least one window ended in a repetition loop, the repeats were left out of the text.
+ */
+repetition: boolean }
+
+/**
+ * A transcribed phrase and the time span it was spoken in, in seconds from the start of the audio.
+ */
+export type TranscriptionSegment = { start: number; end: number; text: string; 
+/**
+ * Generated token ids of the segment, including its timestamp tokens.
+ */
+tokens: number[]; 
+/**
+ * Log-probability of every token of `tokens`.
+ */
+token_logprobs: number[]; 
+/**
+ * Mean of `token_logprobs`, low values mean the model was unsure of the text.
+ */
+avg_logprob: number; 
+/**
+ * Compression ratio of the segment text, high values point to repeated text.
+ */
+compression_ratio: number; 
+/**
+ * Probability that the window of the segment contains no speech, when the model can tell.
+ */
+no_speech_prob: number | null; 
+/**
+ * Word-level timing, only present when word timestamps were requested.
+ */
+words: TranscriptionWord[] | null }
+
+export type TranscriptionWord = { word: string; start: number; end: number }
+
+export type WorkerStatus = { 
+/**
+ * Requests waiting for the worker.
+ */
+queued: number; 
+/**
+ * Whether a request is running right now.
+ */
+running: boolean; 
+/**
+ * Most requests that can wait, further requests are rejected.
+ */
+capacity: number }
+
+/** tauri-specta globals **/
+
+import {
+	invoke as TAURI_INVOKE,
+	Channel as TAURI_CHANNEL,
+} from "@tauri-apps/api/core";
+import * as TAURI_API_EVENT from "@tauri-apps/api/event";
+import { type WebviewWindow as __WebviewWindow__ } from "@tauri-apps/api/webviewWindow";
+
+type __EventObj__<T> = {
+	listen: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.listen<T>>;
+	once: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.once<T>>;
+	emit: null extends T
+		? (payload?: T) => ReturnType<typeof TAURI_API_EVENT.emit>
+		: (payload: T) => ReturnType<typeof TAURI_API_EVENT.emit>;
+};
+
+export type Result<T, E> =
+	| { status: "ok"; data: T }
+	| { status: "error"; error: E };
+
+function __makeEvents__<T extends Record<string, any>>(
+	mappings: Record<keyof T, string>,
+) {
+	return new Proxy(
+		{} as unknown as {
+			[K in keyof T]: __EventObj__<T[K]> & {
+				(handle: __WebviewWindow__): __EventObj__<T[K]>;
+			};
+		},
+		{
+			get: (_, event) => {
+				const name = mappings[event as keyof T];
+
+				return new Proxy((() => {}) as any, {
+					apply: (_, __, [window]: [__WebviewWindow__]) => ({
+						listen: (arg: any) => window.listen(name, arg),
+						once: (arg: any) => window.once(name, arg),
+						emit: (arg: any) => window.emit(name, arg),
+					}),
+					get: (_, command: keyof __EventObj__<any>) => {
+						switch (command) {
+							case "listen":
+								return (arg: any) => TAURI_API_EVENT.listen(name, arg);
+							case "once":
+								return (arg: any) => TAURI_API_EVENT.once(name, arg);
+							case "emit":
+								return (arg: any) => TAURI_API_EVENT.emit(name, arg);
+						}
+					},
+				});
+			},
+		},
+	);
+}
@@ -1,5 +1,7 @@
 import type { InvokeArgs, InvokeOptions } from '@tauri-apps/api/core'
 
+import type { Provider, RequestOptions, SessionOptions, WorkerStatus } from '../bindings/tauri-plugins/ipc-audio-transcription-ort'
+
 import { invoke as tauriInvoke } from '@tauri-apps/api/core'
 
 export interface InvokeMethods {
@@ -7,13 +9,14 @@ export interface InvokeMethods {
   'open_settings_window': { args: undefined, options: undefined, returns: void }
   'open_chat_window': { args: undefined, options: undefined, returns: void }
 
-  // Plugin - Audio Transcription
-  'plugin:ipc-audio-transcription-ort|load_ort_model_whisper': { args: { modelType: 'base' | 'largev3' | 'tiny' | 'medium' }, options: undefined, returns: void }
-  'plugin:ipc-audio-transcription-ort|ipc_audio_transcription': { args: { chunk: number[], language: string }, options: undefined, returns: string }
-
   // Plugin - Audio VAD
-  'plugin:ipc-audio-vad-ort|load_ort_model_silero_vad': { args: undefined, options: undefined, returns: void }
-  'plugin:ipc-audio-vad-ort|ipc_audio_vad': { args: { inputData: { input: number[], sr: number, state: number[] } }, options: undefined, returns: number }
+  'plugin:ipc-audio-vad-ort|load_ort_model_silero_vad': { args: undefined | { modelDir?: string, modelFiles?: Record<string, string> }, options: undefined, returns: void }
+  'plugin:ipc-audio-vad-ort|ipc_audio_vad': { args: { inputData: { input: number[], sr: number, state: number[] }, request?: RequestOptions }, options: undefined, returns: number }
+  'plugin:ipc-audio-vad-ort|cancel_vad': { args: { requestId: string }, options: undefined, returns: boolean }
+  'plugin:ipc-audio-vad-ort|get_vad_queue_status': { args: undefined, options: undefined, returns: WorkerStatus | null }
+  'plugin:ipc-audio-vad-ort|set_vad_session_options': { args: { options: SessionOptions }, options: undefined, returns: void }
+  'plugin:ipc-audio-vad-ort|get_vad_session_options': { args: undefined, options: undefined, returns: Required<SessionOptions> }
+  'plugin:ipc-audio-vad-ort|list_execution_providers': { args: undefined, options: undefined, returns: Provider[] }
 
   // Plugin - Window Pass through on hover
   'plugin:window-pass-through-on-hover|start_tracing_cursor': { args: undefined, options: undefined, returns: void }
@@ -0,0 +1,30 @@
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Prints log records to stderr, keeping stdout for the results.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+  fn enabled(
+    &self,
+    metadata: &Metadata,
+  ) -> bool {
+    metadata.level() <= log::max_level()
+  }
+
+  fn log(
+    &self,
+    record: &Record,
+  ) {
+    if self.enabled(record.metadata()) {
+      eprintln!("[{}] {}", record.level(), record.args());
+    }
+  }
+
+  fn flush(&self) {}
+}
+
+pub fn init(level: LevelFilter) {
+  if log::set_logger(&StderrLogger).is_ok() {
+    log::set_max_level(level);
+  }
+}
@@ -0,0 +1,260 @@
+use std::path::PathBuf;
+
+use airi_audio_transcription_ort::{
+  helpers::audio::load_audio_file,
+  models::{
+    new_whisper_processor,
+    whisper::{
+      precision::ModelPrecision,
+      whisper::{GenerationConfig, Task, TranscriptionOptions},
+      whisper_processor::SAMPLE_RATE,
+    },
+    whisper_model_source,
+  },
+};
+use airi_audio_vad_ort::models::new_silero_vad_processor;
+use airi_ort_common::{
+  model_source::ModelSource,
+  optimized_model_cache::OptimizedModelCache,
+  session_options::{Provider, SessionOptions},
+};
+use anyhow::{Result, anyhow};
+use clap::{Args, Parser, Subcommand};
+use log::{LevelFilter, info};
+
+mod logger;
+mod output;
+mod progress;
+mod vad;
+
+use crate::{
+  output::{Format, print_speech, print_transcription},
+  progress::ConsoleProgress,
+  vad::VadParams,
+};
+
+/// Transcribes audio files or finds the speech in them, with the models of the AIRI desktop app.
+#[derive(Parser, Debug)]
+#[command(name = "airi-asr", version)]
+struct Cli {
+  #[command(subcommand)]
+  command: Command,
+
+  /// Log what the models are doing to stderr.
+  #[arg(short, long, global = true)]
+  verbose: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+  /// Transcribe audio files with Whisper.
+  Transcribe(TranscribeArgs),
+  /// Print the spans of speech in audio files found by Silero VAD.
+  Vad(VadArgs),
+}
+
+/// Where the models come from and how they run, shared by every command.
+#[derive(Args, Debug)]
+struct ModelArgs {
+  /// Local directory laid out like the Hugging Face repository of the model, instead of
+  /// downloading it.
+  #[arg(long)]
+  model_dir: Option<PathBuf>,
+
+  /// Execution providers to run the model on, in order of preference.
+  #[arg(long, value_enum, value_delimiter = ',')]
+  providers: Vec<Provider>,
+
+  /// Threads running a single operator, one per core by default.
+  #[arg(long)]
+  threads: Option<usize>,
+
+  /// Directory to keep the optimized models in, so that later runs start faster.
+  #[arg(long)]
+  cache_dir: Option<PathBuf>,
+}
+
+/// How the results are printed, shared by every command.
+#[derive(Args, Debug)]
+struct OutputArgs {
+  /// Output format.
+  #[arg(short, long, value_enum, default_value_t = Format::Text)]
+  format: Format,
+}
+
+#[derive(Args, Debug)]
+struct TranscribeArgs {
+  /// Audio files to transcribe, in any format the app can read.
+  #[arg(required = true)]
+  files: Vec<PathBuf>,
+
+  /// A known checkpoint (e.g. `base`, `large-v3-turbo`) or the id of a Hugging Face repository
+  /// with a Whisper ONNX export, optionally followed by `@revision`.
+  #[arg(short, long, default_value = "medium")]
+  model: String,
+
+  #[arg(long, value_enum, default_value_t = ModelPrecision::Fp32)]
+  precision: ModelPrecision,
+
+  /// Language code or name, `auto` to detect it.
+  #[arg(short, long)]
+  language: Option<String>,
+
+  #[arg(long, value_enum)]
+  task: Option<Task>,
+
+  /// Align every word to the audio.
+  #[arg(long)]
+  word_timestamps: bool,
+
+  /// Beams kept by beam search, `1` decodes greedily.
+  #[arg(long)]
+  beam_size: Option<usize>,
+
+  /// Text to prompt the first window with, e.g. to bias the spelling of names.
+  #[arg(long)]
+  initial_prompt: Option<String>,
+
+  /// Any other generation setting as the JSON object the app sends, e.g.
+  /// `{"temperatures":[0.0],"no_speech_threshold":0.5}`.
+  #[arg(long)]
+  options: Option<String>,
+
+  #[command(flatten)]
+  model_args: ModelArgs,
+
+  #[command(flatten)]
+  output_args: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct VadArgs {
+  /// Audio files to look for speech in, in any format the app can read.
+  #[arg(required = true)]
+  files: Vec<PathBuf>,
+
+  #[command(flatten)]
+  params: VadParams,
+
+  #[command(flatten)]
+  model_args: ModelArgs,
+
+  #[command(flatten)]
+  output_args: OutputArgs,
+}
+
+impl ModelArgs {
+  fn session_options(
+    &self,
+    defaults: SessionOptions,
+  ) -> SessionOptions {
+    let mut options = SessionOptions {
+      intra_threads: self.threads.or(defaults.intra_threads),
+      ..defaults
+    };
+    if !self.providers.is_empty() {
+      options.providers = self.providers.clone();
+    }
+    options
+  }
+
+  fn cache(&self) -> OptimizedModelCache {
+    OptimizedModelCache::new(self.cache_dir.clone())
+  }
+}
+
+fn transcribe(args: TranscribeArgs) -> Result<()> {
+  let source = match &args.model_args.model_dir {
+    Some(model_dir) => ModelSource::Directory(model_dir.clone()),
+    None => whisper_model_source(&args.model)?,
+  };
+
+  let mut config = GenerationConfig {
+    return_word_timestamps: args.word_timestamps,
+    ..Default::default()
+  };
+  if let Some(options) = &args.options {
+    let options: TranscriptionOptions =
+      serde_json::from_str(options).map_err(|e| anyhow!("Invalid --options: {}", e))?;
+    options.apply(&mut config);
+  }
+  if args.language.is_some() {
+    config.language = args.language;
+  }
+  if let Some(task) = args.task {
+    config.task = task;
+  }
+  if let Some(beam_size) = args.beam_size {
+    config.beam_size = beam_size;
+  }
+  if args.initial_prompt.is_some() {
+    config.initial_prompt = args.initial_prompt;
+  }
+
+  let mut pipeline = new_whisper_processor(
+    ConsoleProgress::shared(),
+    Some(source),
+    args.precision,
+    &args.model_args.session_options(SessionOptions {
+      parallel_execution: true,
+      ..Default::default()
+    }),
+    &args.model_args.cache(),
+  )?;
+
+  for file in &args.files {
+    info!("Transcribing {}...", file.display());
+    let audio = load_audio_file(file, SAMPLE_RATE)?;
+    let result = pipeline.transcribe_with_timestamps(&audio, &config)?;
+    print_transcription(args.output_args.format, file, &result, args.files.len() > 1)?;
+  }
+
+  Ok(())
+}
+
+fn detect_speech(args: VadArgs) -> Result<()> {
+  let source = args
+    .model_args
+    .model_dir
+    .clone()
+    .map(ModelSource::Directory);
+
+  let processor = new_silero_vad_processor(
+    ConsoleProgress::shared(),
+    source,
+    // Frames are tiny, more threads only add synchronization.
+    &args.model_args.session_options(SessionOptions {
+      intra_threads: Some(1),
+      ..Default::default()
+    }),
+    &args.model_args.cache(),
+  )?;
+
+  for file in &args.files {
+    info!("Detecting speech in {}...", file.display());
+    let audio = load_audio_file(file, SAMPLE_RATE)?;
+    let segments = vad::detect_speech(&processor, &audio, SAMPLE_RATE, &args.params)?;
+    print_speech(
+      args.output_args.format,
+      file,
+      &segments,
+      args.files.len() > 1,
+    )?;
+  }
+
+  Ok(())
+}
+
+fn main() -> Result<()> {
+  let cli = Cli::parse();
+  logger::init(if cli.verbose {
+    LevelFilter::Info
+  } else {
+    LevelFilter::Warn
+  });
+
+  match cli.command {
+    Command::Transcribe(args) => transcribe(args),
+    Command::Vad(args) => detect_speech(args),
+  }
+}
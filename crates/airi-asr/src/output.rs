@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use airi_audio_transcription_ort::models::whisper::whisper::TranscriptionResult;
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::vad::SpeechSegment;
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Format {
+  /// Plain text, one line per segment with its time span.
+  Text,
+  /// One JSON object per file and line, the result of the app command plus the file path.
+  Json,
+  /// SubRip subtitles.
+  Srt,
+}
+
+#[derive(Serialize)]
+struct FileResult<'a, T: Serialize> {
+  file:   &'a Path,
+  #[serde(flatten)]
+  result: T,
+}
+
+#[derive(Serialize)]
+struct SpeechResult<'a> {
+  segments: &'a [SpeechSegment],
+}
+
+pub fn print_transcription(
+  format: Format,
+  file: &Path,
+  result: &TranscriptionResult,
+  with_header: bool,
+) -> Result<()> {
+  let cues = result
+    .segments
+    .iter()
+    .map(|segment| (segment.start, segment.end, segment.text.trim()));
+  print_file(format, file, result, cues, with_header)
+}
+
+pub fn print_speech(
+  format: Format,
+  file: &Path,
+  segments: &[SpeechSegment],
+  with_header: bool,
+) -> Result<()> {
+  let cues = segments
+    .iter()
+    .map(|segment| (segment.start, segment.end, "[speech]"));
+  print_file(format, file, SpeechResult { segments }, cues, with_header)
+}
+
+/// Prints the result of one file, text and SRT output of several files are told apart by a
+/// header line with the file path.
+fn print_file<'a>(
+  format: Format,
+  file: &Path,
+  result: impl Serialize,
+  cues: impl Iterator<Item = (f32, f32, &'a str)>,
+  with_header: bool,
+) -> Result<()> {
+  if with_header && !matches!(format, Format::Json) {
+    println!("==> {} <==", file.display());
+  }
+
+  match format {
+    Format::Text => {
+      for (start, end, text) in cues {
+        println!("[{:.2} --> {:.2}] {}", start, end, text);
+      }
+    },
+    Format::Json => println!("{}", serde_json::to_string(&FileResult { file, result })?),
+    Format::Srt => {
+      for (index, (start, end, text)) in cues.enumerate() {
+        println!(
+          "{}\n{} --> {}\n{}\n",
+          index + 1,
+          srt_timestamp(start),
+          srt_timestamp(end),
+          text
+        );
+      }
+    },
+  }
+
+  Ok(())
+}
+
+/// Formats seconds as `HH:MM:SS,mmm`.
+fn srt_timestamp(seconds: f32) -> String {
+  let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+  format!(
+    "{:02}:{:02}:{:02},{:03}",
+    millis / 3_600_000,
+    millis / 60_000 % 60,
+    millis / 1000 % 60,
+    millis % 1000
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn srt_timestamp_splits_hours_minutes_seconds_and_millis() {
+    assert_eq!(srt_timestamp(0.0), "00:00:00,000");
+    assert_eq!(srt_timestamp(61.25), "00:01:01,250");
+    assert_eq!(srt_timestamp(3661.5), "01:01:01,500");
+  }
+
+  #[test]
+  fn srt_timestamp_rounds_to_the_nearest_millisecond() {
+    assert_eq!(srt_timestamp(1.0004), "00:00:01,000");
+    // Rounding up carries into the seconds.
+    assert_eq!(srt_timestamp(1.9996), "00:00:02,000");
+  }
+
+  #[test]
+  fn srt_timestamp_clamps_negative_times_to_zero() {
+    assert_eq!(srt_timestamp(-0.5), "00:00:00,000");
+  }
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use airi_ort_common::huggingface::ProgressEmitter;
+
+/// Reports model downloads on stderr.
+pub struct ConsoleProgress;
+
+impl ConsoleProgress {
+  pub fn shared() -> Arc<Self> {
+    Arc::new(Self)
+  }
+}
+
+impl ProgressEmitter for ConsoleProgress {
+  fn emit_progress(
+    &self,
+    filename: String,
+    progress: f32,
+    total_size: usize,
+    _current_size: usize,
+  ) {
+    eprint!(
+      "\rDownloading {} ({:.1} MB): {:.0}%",
+      filename,
+      total_size as f32 / 1_000_000.0,
+      progress
+    );
+  }
+
+  fn emit_done(
+    &self,
+    filename: String,
+  ) {
+    eprintln!("\rDownloaded {}", filename);
+  }
+}
@@ -0,0 +1,238 @@
+use airi_audio_vad_ort::models::silero_vad::{Processor, VADInferenceInput};
+use anyhow::{Result, anyhow};
+use clap::Args;
+use serde::Serialize;
+
+/// Samples the model looks at per step at 16kHz.
+const FRAME_SIZE: usize = 512;
+/// Size of the recurrent state of the model, `2 * 1 * 128`.
+const STATE_SIZE: usize = 256;
+
+/// Same defaults as the VAD of the app.
+#[derive(Args, Debug)]
+pub struct VadParams {
+  /// Speech starts once its probability reaches this.
+  #[arg(long, default_value_t = 0.3)]
+  pub threshold:      f32,
+  /// Speech ends once its probability drops below this for `--min-silence-ms`.
+  #[arg(long, default_value_t = 0.1)]
+  pub exit_threshold: f32,
+  #[arg(long, default_value_t = 400)]
+  pub min_silence_ms: usize,
+  /// Shorter spans of speech are dropped.
+  #[arg(long, default_value_t = 250)]
+  pub min_speech_ms:  usize,
+  /// Audio kept before and after every span of speech.
+  #[arg(long, default_value_t = 80)]
+  pub speech_pad_ms:  usize,
+}
+
+/// A span of speech, in seconds from the start of the audio.
+#[derive(Serialize, Debug, Clone)]
+pub struct SpeechSegment {
+  pub start: f32,
+  pub end:   f32,
+}
+
+/// Runs the model over `audio` one frame at a time and returns the spans of speech.
+pub fn detect_speech(
+  processor: &Processor,
+  audio: &[f32],
+  sample_rate: usize,
+  params: &VadParams,
+) -> Result<Vec<SpeechSegment>> {
+  let mut state = vec![0.0; STATE_SIZE];
+  let mut probabilities = Vec::with_capacity(audio.len().div_ceil(FRAME_SIZE));
+
+  for frame in audio.chunks(FRAME_SIZE) {
+    let mut input = frame.to_vec();
+    input.resize(FRAME_SIZE, 0.0);
+
+    let result = processor.inference(VADInferenceInput {
+      input,
+      sr: sample_rate as i64,
+      state,
+    })?;
+    state = result.state;
+    probabilities.push(
+      *result
+        .output
+        .first()
+        .ok_or_else(|| anyhow!("VAD model returned no probability"))?,
+    );
+  }
+
+  Ok(speech_segments(
+    &probabilities,
+    audio.len(),
+    sample_rate,
+    params,
+  ))
+}
+
+/// Turns the speech probability of every frame of `FRAME_SIZE` samples into spans of speech, the
+/// last frame may be shorter.
+fn speech_segments(
+  probabilities: &[f32],
+  audio_len: usize,
+  sample_rate: usize,
+  params: &VadParams,
+) -> Vec<SpeechSegment> {
+  let samples_per_ms = sample_rate / 1000;
+  let min_silence = params.min_silence_ms * samples_per_ms;
+  let min_speech = params.min_speech_ms * samples_per_ms;
+  let speech_pad = params.speech_pad_ms * samples_per_ms;
+
+  // Start and end in samples of every span, before padding.
+  let mut spans = Vec::new();
+  let mut speech_start = None;
+  let mut silence_start = None;
+
+  for (index, &probability) in probabilities.iter().enumerate() {
+    let frame_start = index * FRAME_SIZE;
+    let frame_end = (frame_start + FRAME_SIZE).min(audio_len);
+
+    if probability >= params.threshold {
+      speech_start.get_or_insert(frame_start);
+      silence_start = None;
+      continue;
+    }
+
+    let Some(start) = speech_start else {
+      continue;
+    };
+    if probability < params.exit_threshold {
+      let silence = *silence_start.get_or_insert(frame_start);
+      if frame_end - silence >= min_silence {
+        spans.push((start, silence));
+        speech_start = None;
+        silence_start = None;
+      }
+    }
+  }
+  if let Some(start) = speech_start {
+    spans.push((start, audio_len));
+  }
+
+  spans
+    .into_iter()
+    .filter(|(start, end)| end - start >= min_speech)
+    .map(|(start, end)| SpeechSegment {
+      start: start.saturating_sub(speech_pad) as f32 / sample_rate as f32,
+      end:   (end + speech_pad).min(audio_len) as f32 / sample_rate as f32,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE_RATE: usize = 16000;
+
+  /// Frames are 32ms long at 16kHz, so two frames of silence end the speech and spans of a single
+  /// frame are dropped.
+  fn params() -> VadParams {
+    VadParams {
+      threshold:      0.5,
+      exit_threshold: 0.35,
+      min_silence_ms: 64,
+      min_speech_ms:  64,
+      speech_pad_ms:  0,
+    }
+  }
+
+  fn spans(
+    probabilities: &[f32],
+    audio_len: usize,
+    params: &VadParams,
+  ) -> Vec<(f32, f32)> {
+    speech_segments(probabilities, audio_len, SAMPLE_RATE, params)
+      .into_iter()
+      .map(|segment| (segment.start, segment.end))
+      .collect()
+  }
+
+  fn seconds(samples: usize) -> f32 {
+    samples as f32 / SAMPLE_RATE as f32
+  }
+
+  #[test]
+  fn speech_ends_where_the_silence_starts() {
+    let probabilities = [0.1, 0.6, 0.6, 0.6, 0.1, 0.1, 0.1];
+
+    assert_eq!(
+      spans(&probabilities, 7 * FRAME_SIZE, &params()),
+      [(seconds(FRAME_SIZE), seconds(4 * FRAME_SIZE))]
+    );
+  }
+
+  #[test]
+  fn probabilities_between_the_thresholds_keep_the_speech_going() {
+    // Neither starts speech nor counts as silence.
+    assert!(spans(&[0.4, 0.4, 0.4], 3 * FRAME_SIZE, &params()).is_empty());
+
+    let probabilities = [0.6, 0.6, 0.4, 0.4, 0.4, 0.1, 0.1];
+    assert_eq!(
+      spans(&probabilities, 7 * FRAME_SIZE, &params()),
+      [(0.0, seconds(5 * FRAME_SIZE))]
+    );
+  }
+
+  #[test]
+  fn silence_shorter_than_min_silence_does_not_split_the_speech() {
+    let probabilities = [0.6, 0.6, 0.1, 0.6, 0.6, 0.1, 0.1];
+
+    assert_eq!(
+      spans(&probabilities, 7 * FRAME_SIZE, &params()),
+      [(0.0, seconds(5 * FRAME_SIZE))]
+    );
+  }
+
+  #[test]
+  fn speech_shorter_than_min_speech_is_dropped() {
+    let probabilities = [0.1, 0.6, 0.1, 0.1, 0.6, 0.6, 0.1, 0.1];
+
+    assert_eq!(
+      spans(&probabilities, 8 * FRAME_SIZE, &params()),
+      [(seconds(4 * FRAME_SIZE), seconds(6 * FRAME_SIZE))]
+    );
+  }
+
+  #[test]
+  fn padding_stays_within_the_audio() {
+    // 16ms of padding, half a frame.
+    let params = VadParams {
+      speech_pad_ms: 16,
+      ..params()
+    };
+    let probabilities = [0.6, 0.6, 0.1, 0.1, 0.6, 0.6, 0.1, 0.1];
+
+    assert_eq!(
+      spans(&probabilities, 8 * FRAME_SIZE, &params),
+      [
+        (0.0, seconds(2 * FRAME_SIZE + 256)),
+        (seconds(4 * FRAME_SIZE - 256), seconds(6 * FRAME_SIZE + 256)),
+      ]
+    );
+  }
+
+  #[test]
+  fn speech_still_going_at_the_end_lasts_until_the_end_of_the_audio() {
+    // The last frame only has 100 samples.
+    let audio_len = 3 * FRAME_SIZE + 100;
+    let probabilities = [0.1, 0.6, 0.6, 0.6];
+
+    assert_eq!(
+      spans(&probabilities, audio_len, &params()),
+      [(seconds(FRAME_SIZE), seconds(audio_len))]
+    );
+
+    // A silence too short to end the speech is part of it.
+    let probabilities = [0.1, 0.6, 0.6, 0.1];
+    assert_eq!(
+      spans(&probabilities, audio_len, &params()),
+      [(seconds(FRAME_SIZE), seconds(audio_len))]
+    );
+  }
+}
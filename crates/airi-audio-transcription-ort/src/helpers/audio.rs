@@ -0,0 +1,305 @@
+use std::{fs::File, path::Path};
+
+use anyhow::{Result, anyhow};
+use log::warn;
+use rubato::{FftFixedIn, Resampler};
+use symphonia::core::{
+  audio::SampleBuffer,
+  codecs::{CODEC_TYPE_NULL, DecoderOptions},
+  errors::Error as SymphoniaError,
+  formats::FormatOptions,
+  io::MediaSourceStream,
+  meta::MetadataOptions,
+  probe::Hint,
+};
+
+/// Number of input frames the resampler processes at once.
+const RESAMPLE_CHUNK_SIZE: usize = 1024;
+
+/// Decodes the first audio track of a file (WAV, FLAC, OGG/Vorbis, MP3, ...), downmixed to mono
+/// and resampled to `sample_rate`.
+pub fn load_audio_file(
+  path: &Path,
+  sample_rate: usize,
+) -> Result<Vec<f32>> {
+  let file = File::open(path).map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+  let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+  let mut hint = Hint::new();
+  if let Some(extension) = path
+    .extension()
+    .and_then(|extension| extension.to_str())
+  {
+    hint.with_extension(extension);
+  }
+
+  let probed = symphonia::default::get_probe()
+    .format(
+      &hint,
+      stream,
+      &FormatOptions::default(),
+      &MetadataOptions::default(),
+    )
+    .map_err(|e| anyhow!("Unsupported audio format in {}: {}", path.display(), e))?;
+  let mut format = probed.format;
+
+  let track = format
+    .tracks()
+    .iter()
+    .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+    .ok_or_else(|| anyhow!("{} does not contain an audio track", path.display()))?;
+  let track_id = track.id;
+  let mut source_sample_rate = track
+    .codec_params
+    .sample_rate
+    .map(|rate| rate as usize);
+  let mut decoder =
+    symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+  let mut samples = Vec::new();
+  loop {
+    let packet = match format.next_packet() {
+      Ok(packet) => packet,
+      Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+      Err(e) => return Err(e.into()),
+    };
+    if packet.track_id() != track_id {
+      continue;
+    }
+
+    let decoded = match decoder.decode(&packet) {
+      Ok(decoded) => decoded,
+      // Corrupted packets are skipped, the rest of the stream is still usable.
+      Err(SymphoniaError::DecodeError(e)) => {
+        warn!("Skipping undecodable audio packet: {}", e);
+        continue;
+      },
+      Err(e) => return Err(e.into()),
+    };
+
+    let spec = *decoded.spec();
+    source_sample_rate = Some(spec.rate as usize);
+
+    let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+    buffer.copy_interleaved_ref(decoded);
+    samples.extend(downmix_to_mono(buffer.samples(), spec.channels.count()));
+  }
+
+  let source_sample_rate =
+    source_sample_rate.ok_or_else(|| anyhow!("Unknown sample rate for {}", path.display()))?;
+  resample(&samples, source_sample_rate, sample_rate)
+}
+
+/// Converts interleaved audio with any sample rate and channel count to mono at `sample_rate`.
+pub fn convert_to_mono(
+  interleaved: &[f32],
+  source_sample_rate: usize,
+  channels: usize,
+  sample_rate: usize,
+) -> Result<Vec<f32>> {
+  if source_sample_rate == 0 || channels == 0 {
+    return Err(anyhow!(
+      "Invalid audio format: {} Hz with {} channels",
+      source_sample_rate,
+      channels
+    ));
+  }
+
+  resample(
+    &downmix_to_mono(interleaved, channels),
+    source_sample_rate,
+    sample_rate,
+  )
+}
+
+/// Converts a stream of interleaved chunks to mono at `sample_rate`. The resampler is kept from one
+/// chunk to the next, so that their boundaries leave no artifacts and its delay is paid once.
+pub struct StreamConverter {
+  channels:  usize,
+  resampler: Option<FftFixedIn<f32>>,
+  /// Samples of a frame cut short by the end of the last chunk, completed by the next one.
+  partial:   Vec<f32>,
+  /// Mono samples waiting for a whole chunk of the resampler.
+  pending:   Vec<f32>,
+  /// Output samples still to drop at the start of the stream, the delay of the resampler.
+  delay:     usize,
+}
+
+impl StreamConverter {
+  pub fn new(
+    source_sample_rate: usize,
+    channels: usize,
+    sample_rate: usize,
+  ) -> Result<Self> {
+    if source_sample_rate == 0 || channels == 0 {
+      return Err(anyhow!(
+        "Invalid audio format: {} Hz with {} channels",
+        source_sample_rate,
+        channels
+      ));
+    }
+
+    let resampler = if source_sample_rate == sample_rate {
+      None
+    } else {
+      Some(FftFixedIn::<f32>::new(
+        source_sample_rate,
+        sample_rate,
+        RESAMPLE_CHUNK_SIZE,
+        2,
+        1,
+      )?)
+    };
+    let delay = resampler
+      .as_ref()
+      .map_or(0, |resampler| resampler.output_delay());
+
+    Ok(Self {
+      channels,
+      resampler,
+      partial: Vec::new(),
+      pending: Vec::new(),
+      delay,
+    })
+  }
+
+  /// Converts the next chunk. Samples short of a whole frame, or of a whole chunk of the resampler,
+  /// are held back until the next one.
+  pub fn push(
+    &mut self,
+    interleaved: &[f32],
+  ) -> Result<Vec<f32>> {
+    self.partial.extend_from_slice(interleaved);
+    let whole_frames = self.partial.len() - self.partial.len() % self.channels;
+    let mono = downmix_to_mono(&self.partial[..whole_frames], self.channels);
+    self.partial.drain(..whole_frames);
+    let Self {
+      resampler, pending, ..
+    } = self;
+    let Some(resampler) = resampler else {
+      return Ok(mono);
+    };
+
+    pending.extend(mono);
+    let mut output = Vec::new();
+    let mut chunks = pending.chunks_exact(RESAMPLE_CHUNK_SIZE);
+    for chunk in &mut chunks {
+      output.extend_from_slice(&resampler.process(&[chunk], None)?[0]);
+    }
+    let consumed = pending.len() - chunks.remainder().len();
+    pending.drain(..consumed);
+
+    let delayed = self.delay.min(output.len());
+    output.drain(..delayed);
+    self.delay -= delayed;
+    Ok(output)
+  }
+}
+
+/// Averages interleaved frames of `channels` samples into a single channel.
+pub fn downmix_to_mono(
+  interleaved: &[f32],
+  channels: usize,
+) -> Vec<f32> {
+  if channels <= 1 {
+    return interleaved.to_vec();
+  }
+
+  interleaved
+    .chunks_exact(channels)
+    .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+    .collect()
+}
+
+/// Resamples mono audio from `from` Hz to `to` Hz.
+pub fn resample(
+  samples: &[f32],
+  from: usize,
+  to: usize,
+) -> Result<Vec<f32>> {
+  if from == to || samples.is_empty() {
+    return Ok(samples.to_vec());
+  }
+
+  let mut resampler = FftFixedIn::<f32>::new(from, to, RESAMPLE_CHUNK_SIZE, 2, 1)?;
+  let delay = resampler.output_delay();
+  let expected = (samples.len() as u64 * to as u64 / from as u64) as usize;
+
+  let mut output = Vec::with_capacity(expected + delay + RESAMPLE_CHUNK_SIZE);
+  let mut chunks = samples.chunks_exact(RESAMPLE_CHUNK_SIZE);
+  for chunk in &mut chunks {
+    output.extend_from_slice(&resampler.process(&[chunk], None)?[0]);
+  }
+  let remainder = chunks.remainder();
+  if !remainder.is_empty() {
+    output.extend_from_slice(&resampler.process_partial(Some(&[remainder]), None)?[0]);
+  }
+
+  // Flush the samples still held back by the resampler's delay.
+  while output.len() < expected + delay {
+    let flushed = resampler.process_partial::<&[f32]>(None, None)?;
+    if flushed[0].is_empty() {
+      break;
+    }
+    output.extend_from_slice(&flushed[0]);
+  }
+
+  output.drain(..delay.min(output.len()));
+  output.truncate(expected);
+  Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn stream_converter_ignores_chunk_boundaries() {
+    let stereo: Vec<f32> = (0..48_000)
+      .flat_map(|i| {
+        let sample = (i as f32 * 440.0 * std::f32::consts::TAU / 48_000.0).sin();
+        [sample, sample]
+      })
+      .collect();
+
+    let mut whole = StreamConverter::new(48_000, 2, 16_000).unwrap();
+    let expected = whole.push(&stereo).unwrap();
+
+    let mut chunked = StreamConverter::new(48_000, 2, 16_000).unwrap();
+    let mut output = Vec::new();
+    for chunk in stereo.chunks(2 * 333) {
+      output.extend(chunked.push(chunk).unwrap());
+    }
+
+    assert_eq!(output.len(), expected.len());
+    assert!(
+      output
+        .iter()
+        .zip(&expected)
+        .all(|(a, b)| (a - b).abs() < 1e-5)
+    );
+  }
+
+  #[test]
+  fn stream_converter_completes_frames_split_across_chunks() {
+    let mut converter = StreamConverter::new(16_000, 2, 16_000).unwrap();
+
+    // The second frame is split between the chunks.
+    assert_eq!(converter.push(&[0.25, 0.75, 0.5]).unwrap(), vec![0.5]);
+    assert_eq!(converter.push(&[1.0]).unwrap(), vec![0.75]);
+    assert_eq!(converter.push(&[0.5]).unwrap(), Vec::<f32>::new());
+    assert_eq!(
+      converter.push(&[1.0, 0.25, 0.25]).unwrap(),
+      vec![0.75, 0.25]
+    );
+  }
+
+  #[test]
+  fn stream_converter_passes_through_the_target_format() {
+    let mut converter = StreamConverter::new(16_000, 1, 16_000).unwrap();
+    assert_eq!(
+      converter.push(&[0.1, 0.2, 0.3]).unwrap(),
+      vec![0.1, 0.2, 0.3]
+    );
+  }
+}
@@ -0,0 +1,6 @@
+//! Whisper transcription on ONNX Runtime, without Tauri. Download progress of the models is
+//! reported to a [`airi_ort_common::huggingface::ProgressEmitter`], the Tauri plugin forwards it
+//! to the window that loads them.
+
+pub mod helpers;
+pub mod models;
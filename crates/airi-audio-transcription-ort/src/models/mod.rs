@@ -0,0 +1,69 @@
+pub mod whisper;
+
+use std::sync::Arc;
+
+use airi_ort_common::{
+  huggingface::ProgressEmitter,
+  model_source::ModelSource,
+  optimized_model_cache::OptimizedModelCache,
+  session_options::{Provider, SessionOptions},
+};
+use anyhow::anyhow;
+use clap::ValueEnum;
+use log::info;
+
+/// Resolves the model type of the load command: a known checkpoint of
+/// [`whisper::whisper::WhichModel`] (e.g. `base`, `tiny.en`, `distil-large-v3`), or the id of any
+/// Hugging Face repository with a Whisper ONNX export, optionally followed by `@revision`.
+pub fn whisper_model_source(model_type: &str) -> anyhow::Result<ModelSource> {
+  if let Ok(which_model) = whisper::whisper::WhichModel::from_str(model_type, true) {
+    let (model_id, revision) = which_model.model_and_revision();
+    return Ok(ModelSource::hugging_face(model_id, revision));
+  }
+
+  if model_type.contains('/') {
+    let (model_id, revision) = model_type
+      .split_once('@')
+      .unwrap_or((model_type, "main"));
+    return Ok(ModelSource::hugging_face(model_id, revision));
+  }
+
+  let known: Vec<String> = whisper::whisper::WhichModel::value_variants()
+    .iter()
+    .filter_map(|which_model| which_model.to_possible_value())
+    .map(|value| value.get_name().to_string())
+    .collect();
+  Err(anyhow!(
+    "Unknown Whisper model {}, expected one of {} or a Hugging Face repository id",
+    model_type,
+    known.join(", ")
+  ))
+}
+
+pub fn new_whisper_processor(
+  progress: Arc<dyn ProgressEmitter>,
+  source: Option<ModelSource>,
+  precision: whisper::precision::ModelPrecision,
+  session_options: &SessionOptions,
+  cache: &OptimizedModelCache,
+) -> anyhow::Result<whisper::whisper::WhisperPipeline> {
+  let source = source.unwrap_or_else(|| {
+    // Only the providers the sessions are allowed to use count.
+    let usable =
+      |provider: Provider| session_options.providers.contains(&provider) && provider.is_available();
+
+    let whisper_model = if usable(Provider::Cuda) {
+      whisper::whisper::WhichModel::LargeV3
+    } else if usable(Provider::CoreMl) {
+      whisper::whisper::WhichModel::Base
+    } else {
+      whisper::whisper::WhichModel::Tiny
+    };
+
+    let (model_id, revision) = whisper_model.model_and_revision();
+    ModelSource::hugging_face(model_id, revision)
+  });
+
+  info!("Loading {:?} whisper model from {}", precision, source);
+  whisper::whisper::WhisperPipeline::new(source, precision, session_options, cache, progress)
+}
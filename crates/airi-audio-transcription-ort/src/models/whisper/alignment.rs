@@ -0,0 +1,460 @@
+use anyhow::{Result, anyhow};
+use ndarray::{Array2, ArrayView2, ArrayView3, Axis, s};
+use tokenizers::Tokenizer;
+
+use super::{timestamps::TIME_PRECISION, whisper::TranscriptionWord};
+
+/// Width of the median filter applied to the attention weights along the time axis.
+const MEDIAN_FILTER_WIDTH: usize = 7;
+
+/// Punctuation merged into the following word.
+const PREPEND_PUNCTUATIONS: &str = "\"'“¿([{-";
+/// Punctuation merged into the preceding word.
+const APPEND_PUNCTUATIONS: &str = "\"'.。,，!！?？:：”)]}、";
+
+/// Languages written without spaces between words, which are split per character instead.
+pub const LANGUAGES_WITHOUT_SPACES: &[&str] = &["zh", "ja", "th", "lo", "my", "yue"];
+
+/// Decoder positions of the text of `tokens`, where position `first + i` is the one that predicts
+/// `tokens[i]`: the position of every text token, plus the position after the last one, which
+/// marks the end of the last word. The prompt before `first` and the special and timestamp tokens
+/// carry no text, so they are left out of the alignment like in the reference implementation.
+pub fn text_token_positions(
+  tokens: &[i64],
+  first: usize,
+  eos_token_id: i64,
+) -> Vec<usize> {
+  let mut positions: Vec<usize> = tokens
+    .iter()
+    .enumerate()
+    .filter(|&(_, &token)| token < eos_token_id)
+    .map(|(index, _)| first + index)
+    .collect();
+  if let Some(&last) = positions.last() {
+    positions.push(last + 1);
+  }
+  positions
+}
+
+/// Estimates the time (in seconds from the start of the window) at which each of `positions`
+/// starts, by aligning the cross-attention weights of the alignment heads to the audio frames with
+/// dynamic time warping.
+///
+/// `weights` is shaped `[heads, decoder_positions, encoder_frames]`. Every frame is normalized
+/// across all the decoder positions, only `positions` are then smoothed and aligned, see
+/// [`text_token_positions`]. `num_frames` is the number of mel frames that actually hold audio,
+/// the padding after it is ignored.
+pub fn token_timestamps(
+  weights: ArrayView3<f32>,
+  positions: &[usize],
+  num_frames: usize,
+) -> Vec<f32> {
+  let (heads, _, encoder_frames) = weights.dim();
+  if heads == 0 || positions.is_empty() {
+    return Vec::new();
+  }
+
+  // The encoder halves the number of frames.
+  let frames = (num_frames / 2).clamp(1, encoder_frames);
+  let mut weights = weights.slice(s![.., .., ..frames]).to_owned();
+
+  for mut head in weights.outer_iter_mut() {
+    for mut column in head.columns_mut() {
+      let mean = column.mean().unwrap_or(0.0);
+      let std = column.std(0.0);
+      column.mapv_inplace(|w| (w - mean) / std.max(f32::EPSILON));
+    }
+  }
+
+  // Smooth the kept positions along time.
+  let mut weights = weights.select(Axis(1), positions);
+  for mut head in weights.outer_iter_mut() {
+    for mut row in head.rows_mut() {
+      let filtered = median_filter(row.as_slice().unwrap(), MEDIAN_FILTER_WIDTH);
+      row.assign(&ndarray::Array1::from(filtered));
+    }
+  }
+
+  let matrix = weights.mean_axis(Axis(0)).unwrap();
+  let (text_indices, time_indices) = dynamic_time_warping((-matrix).view());
+
+  // Keep the time at which the path first enters each position.
+  let mut timestamps = Vec::with_capacity(positions.len());
+  let mut previous = None;
+  for (text_index, time_index) in text_indices.into_iter().zip(time_indices) {
+    if previous != Some(text_index) {
+      timestamps.push(time_index as f32 * TIME_PRECISION);
+      previous = Some(text_index);
+    }
+  }
+  timestamps
+}
+
+/// Spreads the times of the positions of [`text_token_positions`] over all of `tokens`, plus the
+/// position after the last token, see [`words_from_tokens`]. Special and timestamp tokens take the
+/// time of the text that follows them.
+///
+/// Decoding cut off at the token limit never feeds its last token back to the decoder, so the
+/// position after it has no cross attention. The last word then ends with the window, at
+/// `window_duration`.
+pub fn generated_token_times(
+  tokens: &[i64],
+  text_times: &[f32],
+  eos_token_id: i64,
+  window_duration: f32,
+) -> Result<Vec<f32>> {
+  let num_text_tokens = tokens
+    .iter()
+    .filter(|&&token| token < eos_token_id)
+    .count();
+  if text_times.len() < num_text_tokens {
+    return Err(anyhow!(
+      "cross attentions cover {} text tokens, expected at least {}",
+      text_times.len(),
+      num_text_tokens
+    ));
+  }
+
+  let (text_times, end) = text_times.split_at(num_text_tokens);
+  let mut next = match end.first() {
+    Some(&end) => end,
+    None => window_duration.max(text_times.last().copied().unwrap_or(0.0)),
+  };
+  let mut text_times = text_times.iter().rev();
+
+  let mut token_times = vec![next; tokens.len() + 1];
+  for (index, &token) in tokens.iter().enumerate().rev() {
+    if token < eos_token_id {
+      next = *text_times.next().unwrap();
+    }
+    token_times[index] = next;
+  }
+  Ok(token_times)
+}
+
+/// Groups text tokens into words and assigns each word the time span of its tokens.
+///
+/// `token_times` holds the start time of every token in `tokens` plus one extra entry for the
+/// position right after the last token, which becomes the end of the last word.
+pub fn words_from_tokens(
+  tokenizer: &Tokenizer,
+  tokens: &[i64],
+  token_times: &[f32],
+  eos_token_id: i64,
+  language: Option<&str>,
+) -> Result<Vec<TranscriptionWord>> {
+  if token_times.len() != tokens.len() + 1 {
+    return Err(anyhow!(
+      "expected {} token timestamps, got {}",
+      tokens.len() + 1,
+      token_times.len()
+    ));
+  }
+
+  let split_on_spaces =
+    !language.is_some_and(|language| LANGUAGES_WITHOUT_SPACES.contains(&language));
+
+  // Each word is the text and the token range it covers.
+  let mut words: Vec<(String, usize, usize)> = Vec::new();
+  let mut start = 0;
+  let mut end = 0;
+  while end < tokens.len() {
+    end += 1;
+    if tokens[end - 1] >= eos_token_id {
+      // Special and timestamp tokens are not part of any word.
+      start = end;
+      continue;
+    }
+
+    let text = decode(tokenizer, &tokens[start..end])?;
+    // Wait until the tokens form complete characters.
+    if text.contains('\u{fffd}') && end < tokens.len() {
+      continue;
+    }
+
+    let trimmed = text.trim();
+    let starts_new_word = !split_on_spaces
+      || words.is_empty()
+      || text.starts_with(' ')
+      || trimmed.chars().all(|c| c.is_ascii_punctuation())
+      || words
+        .last()
+        .is_some_and(|&(_, _, last_end)| last_end != start);
+
+    match words.last_mut() {
+      Some((word, _, word_end)) if !starts_new_word => {
+        word.push_str(&text);
+        *word_end = end;
+      },
+      _ => words.push((text, start, end)),
+    }
+    start = end;
+  }
+
+  merge_punctuations(&mut words);
+
+  Ok(
+    words
+      .into_iter()
+      .filter(|(word, ..)| !word.trim().is_empty())
+      .map(|(word, start, end)| TranscriptionWord {
+        word:  word.trim().to_string(),
+        start: token_times[start],
+        end:   token_times[end].max(token_times[start]),
+      })
+      .collect(),
+  )
+}
+
+fn decode(
+  tokenizer: &Tokenizer,
+  tokens: &[i64],
+) -> Result<String> {
+  let tokens: Vec<u32> = tokens
+    .iter()
+    .map(|&token| u32::try_from(token))
+    .collect::<Result<_, _>>()?;
+  tokenizer
+    .decode(&tokens, false)
+    .map_err(|e| anyhow!("Failed to decode tokens: {}", e))
+}
+
+fn merge_punctuations(words: &mut [(String, usize, usize)]) {
+  if words.len() < 2 {
+    return;
+  }
+
+  // Merge leading punctuation into the word that follows it.
+  let mut j = words.len() - 1;
+  for i in (0..words.len() - 1).rev() {
+    let previous = words[i].0.trim();
+    if words[i].0.starts_with(' ')
+      && !previous.is_empty()
+      && previous
+        .chars()
+        .all(|c| PREPEND_PUNCTUATIONS.contains(c))
+    {
+      let (previous, previous_start, _) = std::mem::take(&mut words[i]);
+      words[j].0.insert_str(0, &previous);
+      words[j].1 = previous_start;
+    } else {
+      j = i;
+    }
+  }
+
+  // Merge trailing punctuation into the word before it.
+  let mut i = 0;
+  for j in 1..words.len() {
+    if words[j].0.is_empty() {
+      continue;
+    }
+    if !words[i].0.is_empty()
+      && !words[i].0.ends_with(' ')
+      && words[j]
+        .0
+        .chars()
+        .all(|c| APPEND_PUNCTUATIONS.contains(c))
+    {
+      let (following, _, following_end) = std::mem::take(&mut words[j]);
+      words[i].0.push_str(&following);
+      words[i].2 = following_end;
+    } else {
+      i = j;
+    }
+  }
+}
+
+/// Median filter with reflect padding, as used by the reference implementation.
+fn median_filter(
+  values: &[f32],
+  width: usize,
+) -> Vec<f32> {
+  let pad = width / 2;
+  if values.len() <= pad {
+    return values.to_vec();
+  }
+
+  let reflect = |i: isize| -> f32 {
+    let len = values.len() as isize;
+    let index = if i < 0 {
+      -i
+    } else if i >= len {
+      2 * (len - 1) - i
+    } else {
+      i
+    };
+    values[index as usize]
+  };
+
+  let mut window = vec![0.0; width];
+  (0..values.len())
+    .map(|i| {
+      for (k, w) in window.iter_mut().enumerate() {
+        *w = reflect(i as isize + k as isize - pad as isize);
+      }
+      window.sort_by(|a, b| a.total_cmp(b));
+      window[pad]
+    })
+    .collect()
+}
+
+/// Finds the monotonic path of lowest total cost through `cost`, returning the row and column
+/// index of every step.
+fn dynamic_time_warping(cost: ArrayView2<f32>) -> (Vec<usize>, Vec<usize>) {
+  let (n, m) = cost.dim();
+  let mut total = Array2::<f32>::from_elem((n + 1, m + 1), f32::INFINITY);
+  let mut trace = Array2::<u8>::zeros((n + 1, m + 1));
+  total[[0, 0]] = 0.0;
+
+  for j in 1..=m {
+    for i in 1..=n {
+      let c0 = total[[i - 1, j - 1]];
+      let c1 = total[[i - 1, j]];
+      let c2 = total[[i, j - 1]];
+
+      let (c, t) = if c0 < c1 && c0 < c2 {
+        (c0, 0)
+      } else if c1 < c0 && c1 < c2 {
+        (c1, 1)
+      } else {
+        (c2, 2)
+      };
+
+      total[[i, j]] = cost[[i - 1, j - 1]] + c;
+      trace[[i, j]] = t;
+    }
+  }
+
+  for j in 0..=m {
+    trace[[0, j]] = 2;
+  }
+  for i in 0..=n {
+    trace[[i, 0]] = 1;
+  }
+
+  let (mut i, mut j) = (n, m);
+  let mut text_indices = Vec::with_capacity(n + m);
+  let mut time_indices = Vec::with_capacity(n + m);
+  while i > 0 || j > 0 {
+    text_indices.push(i.saturating_sub(1));
+    time_indices.push(j.saturating_sub(1));
+    match trace[[i, j]] {
+      0 => {
+        i -= 1;
+        j -= 1;
+      },
+      1 => i -= 1,
+      _ => j -= 1,
+    }
+  }
+
+  text_indices.reverse();
+  time_indices.reverse();
+  (text_indices, time_indices)
+}
+
+#[cfg(test)]
+mod tests {
+  use ndarray::{Array3, array};
+
+  use super::*;
+
+  /// Token ids at or above it are special or timestamp tokens.
+  const EOS: i64 = 100;
+
+  #[test]
+  fn text_token_positions_skip_the_prompt_and_the_timestamps() {
+    // A timestamp, two words, a timestamp pair, one word and the closing timestamp.
+    let tokens = [150, 1, 2, 160, 161, 3, 170];
+
+    assert_eq!(text_token_positions(&tokens, 3, EOS), vec![4, 5, 8, 9]);
+  }
+
+  #[test]
+  fn text_token_positions_are_empty_without_text() {
+    assert!(text_token_positions(&[150, EOS], 3, EOS).is_empty());
+  }
+
+  #[test]
+  fn token_timestamps_only_align_the_given_positions() {
+    // The prompt and the timestamp attend to the end of the audio, the first word to the first half
+    // and the second word to the second half.
+    let mut weights = Array3::<f32>::zeros((1, 4, 8));
+    weights[[0, 0, 7]] = 1.0;
+    weights.slice_mut(s![0, 1, ..4]).fill(1.0);
+    weights[[0, 2, 7]] = 1.0;
+    weights.slice_mut(s![0, 3, 4..]).fill(1.0);
+
+    let times = token_timestamps(weights.view(), &[1, 3], 16);
+
+    assert_eq!(times, vec![0.0, 4.0 * TIME_PRECISION]);
+  }
+
+  #[test]
+  fn generated_token_times_give_special_tokens_the_time_of_the_next_text() {
+    let tokens = [150, 1, 2, 160, 161, 3, 170];
+    // The times of the three words and of the position after the last one.
+    let text_times = [0.2, 0.4, 1.0, 1.4];
+
+    let token_times = generated_token_times(&tokens, &text_times, EOS, 30.0).unwrap();
+
+    assert_eq!(token_times, vec![0.2, 0.2, 0.4, 1.0, 1.0, 1.0, 1.4, 1.4]);
+  }
+
+  #[test]
+  fn generated_token_times_end_with_the_window_when_decoding_hit_the_token_limit() {
+    // The last word was never fed back to the decoder.
+    let tokens = [150, 1, 2];
+
+    let token_times = generated_token_times(&tokens, &[0.2, 0.4], EOS, 12.5).unwrap();
+
+    assert_eq!(token_times, vec![0.2, 0.2, 0.4, 12.5]);
+  }
+
+  #[test]
+  fn generated_token_times_fail_without_every_text_token() {
+    assert!(generated_token_times(&[150, 1, 2], &[0.2], EOS, 12.5).is_err());
+  }
+
+  #[test]
+  fn median_filter_removes_spikes_and_reflects_at_the_edges() {
+    let values = [1.0, 9.0, 2.0, 3.0, 8.0, 4.0];
+
+    assert_eq!(
+      median_filter(&values, 3),
+      vec![9.0, 2.0, 3.0, 3.0, 4.0, 8.0]
+    );
+  }
+
+  #[test]
+  fn median_filter_keeps_inputs_shorter_than_its_half_width() {
+    let values = [3.0, 1.0, 2.0];
+
+    assert_eq!(median_filter(&values, 7), values.to_vec());
+  }
+
+  #[test]
+  fn dynamic_time_warping_follows_the_cheapest_monotonic_path() {
+    // Three tokens over four frames, the second token spans the two middle frames.
+    let cost = array![
+      [0.0, 1.0, 1.0, 1.0],
+      [1.0, 0.0, 0.0, 1.0],
+      [1.0, 1.0, 1.0, 0.0],
+    ];
+
+    let (text_indices, time_indices) = dynamic_time_warping(cost.view());
+
+    assert_eq!(text_indices, vec![0, 1, 1, 2]);
+    assert_eq!(time_indices, vec![0, 1, 2, 3]);
+  }
+
+  #[test]
+  fn dynamic_time_warping_covers_every_frame_of_a_single_token() {
+    let cost = array![[0.5, 0.1, 0.3]];
+
+    let (text_indices, time_indices) = dynamic_time_warping(cost.view());
+
+    assert_eq!(text_indices, vec![0, 0, 0]);
+    assert_eq!(time_indices, vec![0, 1, 2]);
+  }
+}
@@ -0,0 +1,297 @@
+use std::borrow::Cow;
+
+use anyhow::{Result, anyhow};
+use ndarray::{Array2, Array3, Array4, Axis, Ix3, Ix4, s};
+use ort::{
+  session::{Session, SessionInputValue},
+  tensor::TensorElementType,
+  value::{DynValue, Tensor},
+};
+
+use super::{
+  precision::{batch_size, extract_f32, input_type, select_batch, tensor_from_f32},
+  whisper::WhisperConfig,
+};
+
+const PAST_KEY_VALUES_PREFIX: &str = "past_key_values.";
+const PRESENT_PREFIX: &str = "present.";
+
+/// Self-attention and cross-attention key/value tensors carried between decoder steps.
+///
+/// Entries are keyed by the decoder input name (`past_key_values.{layer}.{decoder|encoder}.{key|value}`).
+pub struct KvCache {
+  entries:         Vec<(String, DynValue)>,
+  sequence_length: usize,
+}
+
+impl KvCache {
+  /// Number of decoder positions already stored in the self-attention cache.
+  pub fn sequence_length(&self) -> usize {
+    self.sequence_length
+  }
+
+  /// Rearranges the batch so that row `i` holds what row `indices[i]` held before, to follow the
+  /// hypotheses that survived a beam search step. Cross-attention entries are identical for every
+  /// row of the same audio, so they are only gathered when the batch size changes.
+  pub fn reorder(
+    &mut self,
+    indices: &[usize],
+  ) -> Result<()> {
+    for (name, value) in &mut self.entries {
+      if name.contains(".encoder.") && batch_size(value) == Some(indices.len()) {
+        continue;
+      }
+      *value = select_batch(value, indices)?;
+    }
+    Ok(())
+  }
+}
+
+/// Repeats a batch of one along the batch axis, e.g. to feed the same encoder output to every beam.
+pub fn repeat_batch(
+  value: &DynValue,
+  batch_size: usize,
+) -> Result<DynValue> {
+  select_batch(value, &vec![0; batch_size])
+}
+
+pub struct DecoderOutput {
+  /// Logits for every input position, shaped `[batch, input_length, vocab]`.
+  pub logits:           Array3<f32>,
+  /// Cross-attention weights of the requested heads, shaped `[batch, heads, input_length, encoder_length]`.
+  pub cross_attentions: Option<Array4<f32>>,
+  pub cache:            KvCache,
+}
+
+enum DecoderSessions {
+  /// `decoder_model_merged.onnx`, switching between the first pass and the cached passes with `use_cache_branch`.
+  Merged(Session),
+  /// `decoder_model.onnx` for the first pass and `decoder_with_past_model.onnx` for the cached passes.
+  Split {
+    decoder:           Session,
+    decoder_with_past: Session,
+  },
+}
+
+/// The Whisper decoder, run one step at a time with a [`KvCache`] so that each step only
+/// processes the newly generated tokens instead of the whole prefix.
+pub struct WhisperDecoder {
+  sessions:   DecoderSessions,
+  past_names: Vec<String>,
+  /// Element type of the past key/value inputs, `f16` for half precision exports.
+  past_type:  TensorElementType,
+  num_heads:  usize,
+  head_dim:   usize,
+}
+
+impl WhisperDecoder {
+  pub fn merged(
+    session: Session,
+    config: &WhisperConfig,
+  ) -> Result<Self> {
+    let past_names = past_input_names(&session);
+    let past_type = past_input_type(&session, &past_names);
+    Self::new(
+      DecoderSessions::Merged(session),
+      past_names,
+      past_type,
+      config,
+    )
+  }
+
+  pub fn split(
+    decoder: Session,
+    decoder_with_past: Session,
+    config: &WhisperConfig,
+  ) -> Result<Self> {
+    let past_names = past_input_names(&decoder_with_past);
+    let past_type = past_input_type(&decoder_with_past, &past_names);
+    Self::new(
+      DecoderSessions::Split {
+        decoder,
+        decoder_with_past,
+      },
+      past_names,
+      past_type,
+      config,
+    )
+  }
+
+  fn new(
+    sessions: DecoderSessions,
+    past_names: Vec<String>,
+    past_type: TensorElementType,
+    config: &WhisperConfig,
+  ) -> Result<Self> {
+    if past_names.is_empty() {
+      return Err(anyhow!(
+        "decoder model does not accept any past_key_values inputs"
+      ));
+    }
+    if config.decoder_attention_heads == 0 || config.d_model % config.decoder_attention_heads != 0 {
+      return Err(anyhow!(
+        "d_model ({}) is not divisible by decoder_attention_heads ({})",
+        config.d_model,
+        config.decoder_attention_heads
+      ));
+    }
+
+    Ok(Self {
+      sessions,
+      past_names,
+      past_type,
+      num_heads: config.decoder_attention_heads,
+      head_dim: config.d_model / config.decoder_attention_heads,
+    })
+  }
+
+  /// Runs one decoder step.
+  ///
+  /// Without a cache `input_ids` is the whole prompt; with a cache it only holds the tokens
+  /// generated since the previous step. When `alignment_heads` (pairs of layer and head) is
+  /// given, the cross-attention weights of those heads are returned as well, which requires a
+  /// model exported with `cross_attentions.{layer}` outputs.
+  pub fn forward(
+    &mut self,
+    input_ids: Array2<i64>,
+    encoder_hidden_states: &DynValue,
+    cache: Option<KvCache>,
+    alignment_heads: Option<&[(usize, usize)]>,
+  ) -> Result<DecoderOutput> {
+    let (batch_size, input_length) = input_ids.dim();
+    let past_sequence_length = cache.as_ref().map_or(0, KvCache::sequence_length);
+
+    let session = match (&mut self.sessions, cache.is_some()) {
+      (DecoderSessions::Merged(session), _) => session,
+      (DecoderSessions::Split { decoder, .. }, false) => decoder,
+      (
+        DecoderSessions::Split {
+          decoder_with_past, ..
+        },
+        true,
+      ) => decoder_with_past,
+    };
+
+    let accepts = |name: &str| {
+      session
+        .inputs
+        .iter()
+        .any(|input| input.name == name)
+    };
+
+    let mut inputs: Vec<(Cow<'_, str>, SessionInputValue<'_>)> =
+      Vec::with_capacity(3 + self.past_names.len());
+    inputs.push(("input_ids".into(), Tensor::from_array(input_ids)?.into()));
+    if accepts("encoder_hidden_states") {
+      inputs.push(("encoder_hidden_states".into(), encoder_hidden_states.into()));
+    }
+    if accepts("use_cache_branch") {
+      inputs.push((
+        "use_cache_branch".into(),
+        Tensor::from_array(([1], vec![cache.is_some()]))?.into(),
+      ));
+    }
+
+    match &cache {
+      Some(cache) => {
+        for (name, value) in &cache.entries {
+          if accepts(name) {
+            inputs.push((name.as_str().into(), value.into()));
+          }
+        }
+      },
+      // The merged decoder still expects the past inputs on its first pass, they are just empty.
+      None if accepts(&self.past_names[0]) => {
+        for name in &self.past_names {
+          let empty = tensor_from_f32(
+            Array4::<f32>::zeros((batch_size, self.num_heads, 0, self.head_dim)),
+            self.past_type,
+          )?;
+          inputs.push((name.as_str().into(), empty.into()));
+        }
+      },
+      None => {},
+    }
+
+    let mut outputs = session.run(inputs)?;
+
+    let logits = extract_f32(
+      outputs
+        .get("logits")
+        .ok_or_else(|| anyhow!("decoder output did not contain 'logits'"))?,
+    )?
+    .into_dimensionality::<Ix3>()?;
+
+    let cross_attentions = match alignment_heads {
+      Some(alignment_heads) => {
+        let mut heads = Vec::with_capacity(alignment_heads.len());
+        for &(layer, head) in alignment_heads {
+          let name = format!("cross_attentions.{}", layer);
+          let attentions = extract_f32(outputs.get(&name).ok_or_else(|| {
+            anyhow!(
+              "decoder output did not contain '{}', word timestamps need a model exported with cross attentions (e.g. a *_timestamped export)",
+              name
+            )
+          })?)?
+          .into_dimensionality::<Ix4>()?;
+          heads.push(attentions.slice(s![.., head, .., ..]).to_owned());
+        }
+        let views: Vec<_> = heads.iter().map(|head| head.view()).collect();
+        Some(ndarray::stack(Axis(1), &views)?)
+      },
+      None => None,
+    };
+
+    let mut previous = cache.map(|cache| cache.entries);
+    let mut entries = Vec::with_capacity(self.past_names.len());
+    for name in &self.past_names {
+      // Cross-attention keys/values only depend on the encoder output, so they are computed on the
+      // first pass and reused afterwards (the cached branch does not output meaningful values for them).
+      let reused = previous
+        .as_mut()
+        .filter(|_| name.contains(".encoder."))
+        .and_then(|previous| {
+          let index = previous.iter().position(|(n, _)| n == name)?;
+          Some(previous.swap_remove(index).1)
+        });
+
+      let value = match reused {
+        Some(value) => value,
+        None => {
+          let present_name = name.replacen(PAST_KEY_VALUES_PREFIX, PRESENT_PREFIX, 1);
+          outputs
+            .remove(&present_name)
+            .ok_or_else(|| anyhow!("decoder output did not contain '{}'", present_name))?
+        },
+      };
+      entries.push((name.clone(), value));
+    }
+
+    Ok(DecoderOutput {
+      logits,
+      cross_attentions,
+      cache: KvCache {
+        entries,
+        sequence_length: past_sequence_length + input_length,
+      },
+    })
+  }
+}
+
+fn past_input_type(
+  session: &Session,
+  past_names: &[String],
+) -> TensorElementType {
+  past_names
+    .first()
+    .map_or(TensorElementType::Float32, |name| input_type(session, name))
+}
+
+fn past_input_names(session: &Session) -> Vec<String> {
+  session
+    .inputs
+    .iter()
+    .filter(|input| input.name.starts_with(PAST_KEY_VALUES_PREFIX))
+    .map(|input| input.name.clone())
+    .collect()
+}
@@ -0,0 +1,351 @@
+use std::io::Write;
+
+use flate2::{Compression, write::ZlibEncoder};
+use ndarray::{Array1, ArrayView1, ArrayView2};
+use rand::Rng;
+
+/// A decoded token sequence (without the prompt and the end of text token) and the sum of the
+/// log-probabilities of its tokens.
+#[derive(Debug, Clone, Default)]
+pub struct Hypothesis {
+  pub tokens:         Vec<i64>,
+  /// Log-probability of every token of `tokens`.
+  pub token_logprobs: Vec<f32>,
+  /// Sum of the token log-probabilities, including the end of text token once it was generated.
+  pub sum_logprob:    f32,
+}
+
+impl Hypothesis {
+  /// Keeps the first `length` tokens, which also drops the end of text token from the sum.
+  pub fn truncate(
+    &mut self,
+    length: usize,
+  ) {
+    self.tokens.truncate(length);
+    self.token_logprobs.truncate(length);
+    self.sum_logprob = self.token_logprobs.iter().sum();
+  }
+
+  /// Average log-probability per token, counting the end of text token the same way the reference
+  /// implementation does.
+  pub fn avg_logprob(&self) -> f32 {
+    self.sum_logprob / (self.tokens.len() + 1) as f32
+  }
+}
+
+pub fn log_softmax(logits: ArrayView1<f32>) -> Array1<f32> {
+  let max = logits.fold(f32::NEG_INFINITY, |max, &logit| max.max(logit));
+  let log_sum = logits
+    .iter()
+    .map(|&logit| (logit - max).exp())
+    .sum::<f32>()
+    .ln();
+  logits.mapv(|logit| logit - max - log_sum)
+}
+
+pub fn argmax(values: ArrayView1<f32>) -> usize {
+  values
+    .iter()
+    .enumerate()
+    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+    .map_or(0, |(index, _)| index)
+}
+
+/// Picks the next token, greedily when `temperature` is zero, otherwise by sampling from the
+/// distribution of `logits / temperature`.
+pub fn sample_token(
+  logits: ArrayView1<f32>,
+  temperature: f32,
+  rng: &mut impl Rng,
+) -> usize {
+  if temperature <= 0.0 {
+    return argmax(logits);
+  }
+
+  let max = logits.fold(f32::NEG_INFINITY, |max, &logit| max.max(logit));
+  let weights: Vec<f32> = logits
+    .iter()
+    .map(|&logit| ((logit - max) / temperature).exp())
+    .collect();
+
+  let mut remaining = rng.random::<f32>() * weights.iter().sum::<f32>();
+  for (index, weight) in weights.iter().enumerate() {
+    if remaining < *weight {
+      return index;
+    }
+    remaining -= weight;
+  }
+  argmax(logits)
+}
+
+/// Ratio between the size of the text and the size of its zlib-compressed form. Repetitive output
+/// (a model stuck in a loop) compresses very well and ends up with a high ratio.
+pub fn compression_ratio(text: &str) -> f32 {
+  if text.is_empty() {
+    return 0.0;
+  }
+
+  let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+  let compressed_length = encoder
+    .write_all(text.as_bytes())
+    .and_then(|_| encoder.finish())
+    .map_or(text.len(), |compressed| compressed.len());
+  text.len() as f32 / compressed_length as f32
+}
+
+/// Longest n-gram considered when looking for a repetition loop.
+const MAX_LOOP_NGRAM: usize = 16;
+/// Short n-grams only count as a loop once their repeats span this many tokens, so that genuine
+/// repetitions like "no, no, no" are kept.
+const MIN_LOOP_TOKENS: usize = 12;
+
+/// Looks for a repetition loop at the end of `tokens`, the same n-gram repeated at least
+/// `min_repeats` times in a row, possibly followed by the start of one more repeat. Returns the
+/// number of tokens to keep: everything up to the end of the first occurrence of the n-gram.
+pub fn repetition_loop(
+  tokens: &[i64],
+  min_repeats: usize,
+) -> Option<usize> {
+  for ngram in 1..=MAX_LOOP_NGRAM.min(tokens.len() / 2) {
+    let required = min_repeats
+      .max(MIN_LOOP_TOKENS.div_ceil(ngram))
+      .max(2);
+
+    // Walk back to the start of the tail that repeats every `ngram` tokens, so that the loop is
+    // cut at the same place whichever phase the output stopped in.
+    let mut start = tokens.len() - ngram;
+    while start > 0 && tokens[start - 1] == tokens[start - 1 + ngram] {
+      start -= 1;
+    }
+    if (tokens.len() - start) / ngram >= required {
+      return Some(start + ngram);
+    }
+  }
+  None
+}
+
+/// Score used to rank finished beams. Without a length penalty the log-probability is divided by
+/// the length, otherwise by the penalty from Google's NMT paper, `((5 + length) / 6) ^ penalty`.
+pub fn length_penalized_score(
+  hypothesis: &Hypothesis,
+  length_penalty: Option<f32>,
+) -> f32 {
+  let length = hypothesis.tokens.len().max(1) as f32;
+  let penalty = match length_penalty {
+    Some(length_penalty) => ((5.0 + length) / 6.0).powf(length_penalty),
+    None => length,
+  };
+  hypothesis.sum_logprob / penalty
+}
+
+/// Beam search over a single audio input, following the reference implementation: at every step
+/// each beam proposes its `beam_size + 1` best continuations, the best `beam_size` unfinished ones
+/// survive, and candidates ending with the end of text token are collected until there are
+/// `beam_size * patience` of them.
+pub struct BeamSearch {
+  beam_size:      usize,
+  max_candidates: usize,
+  eos_token_id:   i64,
+  beams:          Vec<Hypothesis>,
+  finished:       Vec<Hypothesis>,
+}
+
+impl BeamSearch {
+  pub fn new(
+    beam_size: usize,
+    patience: f32,
+    eos_token_id: i64,
+  ) -> Self {
+    let beam_size = beam_size.max(1);
+    Self {
+      beam_size,
+      max_candidates: ((beam_size as f32 * patience).round() as usize).max(1),
+      eos_token_id,
+      beams: vec![Hypothesis::default()],
+      finished: Vec::new(),
+    }
+  }
+
+  /// Number of live beams, the batch size of the next decoder step.
+  pub fn num_beams(&self) -> usize {
+    self.beams.len()
+  }
+
+  /// The token each live beam ended with, i.e. the decoder input of the next step.
+  pub fn last_tokens(&self) -> Vec<i64> {
+    self
+      .beams
+      .iter()
+      .map(|beam| *beam.tokens.last().unwrap_or(&self.eos_token_id))
+      .collect()
+  }
+
+  /// The tokens generated so far by the live beam at `index`.
+  pub fn tokens(
+    &self,
+    index: usize,
+  ) -> &[i64] {
+    &self.beams[index].tokens
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.finished.len() >= self.max_candidates
+  }
+
+  /// Extends the beams with the log-probabilities of the next token (one row per live beam) and
+  /// returns, for every new beam, the index of the beam it extends, so that the decoder cache can
+  /// be reordered to match.
+  pub fn step(
+    &mut self,
+    logprobs: ArrayView2<f32>,
+  ) -> Vec<usize> {
+    let mut candidates: Vec<(f32, usize, i64, f32)> = Vec::new();
+    for (source, (beam, logprobs)) in self
+      .beams
+      .iter()
+      .zip(logprobs.outer_iter())
+      .enumerate()
+    {
+      let mut tokens: Vec<usize> = (0..logprobs.len()).collect();
+      let top = (self.beam_size + 1).min(tokens.len());
+      tokens.select_nth_unstable_by(top - 1, |&a, &b| logprobs[b].total_cmp(&logprobs[a]));
+      for &token in &tokens[..top] {
+        candidates.push((
+          beam.sum_logprob + logprobs[token],
+          source,
+          token as i64,
+          logprobs[token],
+        ));
+      }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut beams = Vec::with_capacity(self.beam_size);
+    let mut sources = Vec::with_capacity(self.beam_size);
+    for (sum_logprob, source, token, logprob) in candidates {
+      if token == self.eos_token_id {
+        if self.finished.len() < self.max_candidates {
+          self.finished.push(Hypothesis {
+            sum_logprob,
+            ..self.beams[source].clone()
+          });
+        }
+        continue;
+      }
+
+      let mut beam = self.beams[source].clone();
+      beam.tokens.push(token);
+      beam.token_logprobs.push(logprob);
+      beam.sum_logprob = sum_logprob;
+      beams.push(beam);
+      sources.push(source);
+      if beams.len() == self.beam_size {
+        break;
+      }
+    }
+
+    self.beams = beams;
+    sources
+  }
+
+  /// Picks the best hypothesis, falling back to the unfinished beams when too few finished in time.
+  pub fn finish(
+    mut self,
+    length_penalty: Option<f32>,
+  ) -> Hypothesis {
+    if self.finished.len() < self.beam_size {
+      self
+        .beams
+        .sort_by(|a, b| b.sum_logprob.total_cmp(&a.sum_logprob));
+      let missing = self.beam_size - self.finished.len();
+      self
+        .finished
+        .extend(self.beams.into_iter().take(missing));
+    }
+
+    self
+      .finished
+      .into_iter()
+      .max_by(|a, b| {
+        length_penalized_score(a, length_penalty)
+          .total_cmp(&length_penalized_score(b, length_penalty))
+      })
+      .unwrap_or_default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ndarray::array;
+
+  use super::*;
+
+  const EOS: i64 = 0;
+
+  #[test]
+  fn beam_search_keeps_the_best_continuations_in_order() {
+    let mut search = BeamSearch::new(2, 1.0, EOS);
+    // The end of text token is the best candidate, the two next ones become the beams.
+    let sources = search.step(array![[-0.1, -1.0, -2.0, -3.0]].view());
+
+    assert_eq!(sources, vec![0, 0]);
+    assert_eq!(search.last_tokens(), vec![1, 2]);
+    assert!(!search.is_finished());
+
+    // The second beam's continuation outranks the first beam's second best one.
+    let sources = search.step(array![[-0.5, -1.0, -3.0, -4.0], [-5.0, -0.1, -6.0, -7.0],].view());
+
+    assert_eq!(sources, vec![0, 1]);
+    assert_eq!(search.tokens(0), &[1, 1]);
+    assert_eq!(search.tokens(1), &[2, 1]);
+    assert!(search.is_finished());
+  }
+
+  #[test]
+  fn beam_search_picks_the_best_finished_hypothesis() {
+    let mut search = BeamSearch::new(2, 1.0, EOS);
+    search.step(array![[-0.1, -1.0, -2.0, -3.0]].view());
+    search.step(array![[-0.5, -1.0, -3.0, -4.0], [-5.0, -0.1, -6.0, -7.0],].view());
+
+    // Finished: the empty hypothesis (-0.1) and [1] (-1.5), the live beams are ignored.
+    let best = search.finish(None);
+
+    assert!(best.tokens.is_empty());
+    assert!((best.sum_logprob + 0.1).abs() < 1e-6);
+  }
+
+  #[test]
+  fn beam_search_falls_back_to_the_live_beams_when_too_few_finished() {
+    let mut search = BeamSearch::new(2, 1.0, EOS);
+    // The end of text token does not make it past the two better continuations.
+    search.step(array![[-3.0, -0.5, -1.0, -4.0]].view());
+    search.step(array![[-4.0, -0.2, -3.0, -5.0], [-4.0, -3.0, -0.1, -5.0],].view());
+
+    let best = search.finish(None);
+
+    assert_eq!(best.tokens, vec![1, 1]);
+    assert_eq!(best.token_logprobs, vec![-0.5, -0.2]);
+  }
+
+  #[test]
+  fn repetition_loop_keeps_the_first_occurrence_of_the_repeated_ngram() {
+    // "1 2 3" four times, cut off in the middle of a fifth repeat.
+    let tokens = [5, 6, 1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 2];
+
+    assert_eq!(repetition_loop(&tokens, 2), Some(5));
+  }
+
+  #[test]
+  fn repetition_loop_finds_long_ngrams_repeated_twice() {
+    let tokens = [9, 1, 2, 3, 4, 5, 6, 1, 2, 3, 4, 5, 6];
+
+    assert_eq!(repetition_loop(&tokens, 2), Some(7));
+  }
+
+  #[test]
+  fn repetition_loop_ignores_short_genuine_repetitions() {
+    // "no, no, no" spans too few tokens to be a loop.
+    assert_eq!(repetition_loop(&[4, 7, 8, 7, 8, 7, 8], 3), None);
+    assert_eq!(repetition_loop(&[1, 2, 3, 4], 2), None);
+  }
+}
@@ -0,0 +1,265 @@
+use ndarray::{ArrayViewMut1, s};
+
+/// Adjusts the logits of the next token before it gets picked, usually by masking tokens with
+/// negative infinity.
+///
+/// `generated` holds the tokens generated so far for this sequence, without the prompt.
+pub trait LogitsProcessor: Send + Sync {
+  fn process(
+    &self,
+    generated: &[i64],
+    logits: ArrayViewMut1<f32>,
+  );
+}
+
+/// Runs a list of [`LogitsProcessor`]s in order.
+#[derive(Default)]
+pub struct LogitsProcessorChain {
+  processors: Vec<Box<dyn LogitsProcessor>>,
+}
+
+impl LogitsProcessorChain {
+  pub fn push(
+    &mut self,
+    processor: impl LogitsProcessor + 'static,
+  ) {
+    self.processors.push(Box::new(processor));
+  }
+
+  pub fn process(
+    &self,
+    generated: &[i64],
+    mut logits: ArrayViewMut1<f32>,
+  ) {
+    for processor in &self.processors {
+      processor.process(generated, logits.view_mut());
+    }
+  }
+}
+
+fn suppress(
+  logits: &mut ArrayViewMut1<f32>,
+  tokens: &[i64],
+) {
+  for &token in tokens {
+    if let Some(logit) = usize::try_from(token)
+      .ok()
+      .and_then(|token| logits.get_mut(token))
+    {
+      *logit = f32::NEG_INFINITY;
+    }
+  }
+}
+
+/// Never generates the given tokens, e.g. the non-speech symbols listed in `suppress_tokens`.
+pub struct SuppressTokens {
+  pub tokens: Vec<i64>,
+}
+
+impl LogitsProcessor for SuppressTokens {
+  fn process(
+    &self,
+    _generated: &[i64],
+    mut logits: ArrayViewMut1<f32>,
+  ) {
+    suppress(&mut logits, &self.tokens);
+  }
+}
+
+/// Keeps the output from starting with the given tokens, usually a blank and the end of text
+/// token (`begin_suppress_tokens`), so that the model does not give up on the audio right away.
+pub struct SuppressBlank {
+  pub tokens: Vec<i64>,
+}
+
+impl LogitsProcessor for SuppressBlank {
+  fn process(
+    &self,
+    generated: &[i64],
+    mut logits: ArrayViewMut1<f32>,
+  ) {
+    if generated.is_empty() {
+      suppress(&mut logits, &self.tokens);
+    }
+  }
+}
+
+/// Enforces a well-formed timestamp sequence, following the reference implementation:
+///
+/// - the output starts with a timestamp, no later than `max_initial_timestamp_index` steps,
+/// - timestamps come in pairs (the end of a segment and the start of the next one), except for the
+///   last one before the end of text,
+/// - timestamps never decrease, and every segment lasts at least one step,
+/// - a timestamp is forced whenever all timestamps together are more likely than any text token.
+pub struct TimestampRules {
+  pub timestamp_begin:             i64,
+  pub no_timestamps_token_id:      i64,
+  pub eos_token_id:                i64,
+  pub max_initial_timestamp_index: Option<usize>,
+}
+
+impl LogitsProcessor for TimestampRules {
+  fn process(
+    &self,
+    generated: &[i64],
+    mut logits: ArrayViewMut1<f32>,
+  ) {
+    let vocab_size = logits.len();
+    let (Ok(timestamp_begin), Ok(eos_token_id)) = (
+      usize::try_from(self.timestamp_begin),
+      usize::try_from(self.eos_token_id),
+    ) else {
+      return;
+    };
+    if timestamp_begin >= vocab_size {
+      return;
+    }
+
+    suppress(&mut logits, &[self.no_timestamps_token_id]);
+
+    let is_timestamp = |token: &i64| *token >= self.timestamp_begin;
+    let last_was_timestamp = generated.last().is_some_and(is_timestamp);
+    let penultimate_was_timestamp =
+      generated.len() < 2 || is_timestamp(&generated[generated.len() - 2]);
+
+    if last_was_timestamp {
+      if penultimate_was_timestamp {
+        // A pair of timestamps was just closed, the next token has to be text.
+        logits
+          .slice_mut(s![timestamp_begin..])
+          .fill(f32::NEG_INFINITY);
+      } else {
+        // A single timestamp ends a segment, it is followed by another timestamp or the end.
+        logits
+          .slice_mut(s![..eos_token_id.min(vocab_size)])
+          .fill(f32::NEG_INFINITY);
+      }
+    }
+
+    if let Some(&last_timestamp) = generated
+      .iter()
+      .rev()
+      .find(|token| is_timestamp(token))
+    {
+      let last_timestamp = usize::try_from(last_timestamp).unwrap_or(timestamp_begin);
+      let earliest = if last_was_timestamp && !penultimate_was_timestamp {
+        last_timestamp
+      } else {
+        last_timestamp + 1
+      };
+      logits
+        .slice_mut(s![timestamp_begin..earliest.min(vocab_size)])
+        .fill(f32::NEG_INFINITY);
+    }
+
+    if generated.is_empty() {
+      logits
+        .slice_mut(s![..timestamp_begin])
+        .fill(f32::NEG_INFINITY);
+      if let Some(max_initial_timestamp_index) = self.max_initial_timestamp_index {
+        let last_allowed = timestamp_begin + max_initial_timestamp_index;
+        if last_allowed + 1 < vocab_size {
+          logits
+            .slice_mut(s![last_allowed + 1..])
+            .fill(f32::NEG_INFINITY);
+        }
+      }
+    }
+
+    // Compare the total probability of the timestamps with the best text token.
+    let max = logits.fold(f32::NEG_INFINITY, |max, &logit| max.max(logit));
+    if max == f32::NEG_INFINITY {
+      return;
+    }
+    let timestamp_mass: f32 = logits
+      .slice(s![timestamp_begin..])
+      .iter()
+      .map(|&logit| (logit - max).exp())
+      .sum();
+    let max_text = logits
+      .slice(s![..timestamp_begin])
+      .fold(f32::NEG_INFINITY, |max, &logit| max.max(logit));
+    if timestamp_mass.ln() + max > max_text {
+      logits
+        .slice_mut(s![..timestamp_begin])
+        .fill(f32::NEG_INFINITY);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ndarray::Array1;
+
+  use super::*;
+
+  // Text tokens 0..5, then the end of text token, the no timestamps token and five timestamps.
+  const EOS: i64 = 5;
+  const TIMESTAMP_BEGIN: usize = 7;
+  const VOCAB_SIZE: usize = 12;
+
+  fn rules() -> TimestampRules {
+    TimestampRules {
+      timestamp_begin:             TIMESTAMP_BEGIN as i64,
+      no_timestamps_token_id:      6,
+      eos_token_id:                EOS,
+      max_initial_timestamp_index: Some(1),
+    }
+  }
+
+  /// Tokens left allowed after processing logits that favor text, so that the rule forcing a
+  /// timestamp stays out of the way.
+  fn allowed(generated: &[i64]) -> Vec<usize> {
+    let mut logits = Array1::from_shape_fn(VOCAB_SIZE, |token| {
+      if token < TIMESTAMP_BEGIN {
+        10.0
+      } else {
+        0.0
+      }
+    });
+    rules().process(generated, logits.view_mut());
+    (0..VOCAB_SIZE)
+      .filter(|&token| logits[token].is_finite())
+      .collect()
+  }
+
+  #[test]
+  fn timestamp_rules_start_with_an_early_timestamp() {
+    assert_eq!(allowed(&[]), vec![7, 8]);
+  }
+
+  #[test]
+  fn timestamp_rules_close_a_segment_with_a_second_timestamp_or_the_end() {
+    // A segment ended at timestamp 9, the next one cannot start earlier.
+    assert_eq!(allowed(&[7, 1, 2, 9]), vec![5, 9, 10, 11]);
+  }
+
+  #[test]
+  fn timestamp_rules_follow_a_pair_of_timestamps_with_text() {
+    assert_eq!(allowed(&[7, 1, 9, 9]), vec![0, 1, 2, 3, 4, 5]);
+  }
+
+  #[test]
+  fn timestamp_rules_keep_timestamps_increasing() {
+    assert_eq!(allowed(&[7, 1, 2]), vec![0, 1, 2, 3, 4, 5, 8, 9, 10, 11]);
+  }
+
+  #[test]
+  fn timestamp_rules_force_a_timestamp_when_they_outweigh_the_text() {
+    let mut logits = Array1::zeros(VOCAB_SIZE);
+    rules().process(&[7, 1], logits.view_mut());
+
+    assert!(
+      logits
+        .slice(s![..TIMESTAMP_BEGIN])
+        .iter()
+        .all(|logit| logit.is_infinite())
+    );
+    assert!(
+      logits
+        .slice(s![TIMESTAMP_BEGIN + 1..])
+        .iter()
+        .all(|logit| logit.is_finite())
+    );
+  }
+}
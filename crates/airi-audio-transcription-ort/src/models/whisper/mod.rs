@@ -0,0 +1,9 @@
+pub mod alignment;
+pub mod decoder;
+pub mod decoding;
+pub mod logits_processor;
+pub mod precision;
+pub mod streaming;
+pub mod timestamps;
+pub mod whisper;
+pub mod whisper_processor;
@@ -0,0 +1,127 @@
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use half::f16;
+use ndarray::{Array, ArrayD, Axis, Dimension};
+use ort::{
+  session::Session,
+  tensor::TensorElementType,
+  value::{DynValue, Tensor, ValueType},
+};
+use serde::{Deserialize, Serialize};
+
+/// Which variant of the ONNX export to load. Hugging Face exports (e.g. `onnx-community/*`) ship
+/// every variant next to each other, told apart by a suffix of the file name
+/// (`onnx/encoder_model_q4.onnx`).
+///
+/// The quantized variants are several times smaller and much faster on CPU, the half precision
+/// ones are meant for GPUs.
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize, specta::Type,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelPrecision {
+  /// Full precision weights, `*.onnx`.
+  #[default]
+  Fp32,
+  /// Half precision weights, inputs and outputs, `*_fp16.onnx`.
+  Fp16,
+  /// Dynamically quantized 8-bit weights, `*_quantized.onnx`.
+  Quantized,
+  /// Signed 8-bit weights, `*_int8.onnx`.
+  Int8,
+  /// Unsigned 8-bit weights, `*_uint8.onnx`.
+  Uint8,
+  /// 4-bit weights with full precision activations, `*_q4.onnx`.
+  Q4,
+  /// 4-bit weights with half precision activations, `*_q4f16.onnx`.
+  Q4f16,
+  /// 4-bit weights quantized with bitsandbytes, `*_bnb4.onnx`.
+  Bnb4,
+}
+
+impl ModelPrecision {
+  pub fn file_suffix(&self) -> &'static str {
+    match self {
+      Self::Fp32 => "",
+      Self::Fp16 => "_fp16",
+      Self::Quantized => "_quantized",
+      Self::Int8 => "_int8",
+      Self::Uint8 => "_uint8",
+      Self::Q4 => "_q4",
+      Self::Q4f16 => "_q4f16",
+      Self::Bnb4 => "_bnb4",
+    }
+  }
+
+  /// Path of the model `name` (e.g. `encoder_model`) of this variant in the repository layout.
+  pub fn model_file(
+    &self,
+    name: &str,
+  ) -> String {
+    format!("onnx/{}{}.onnx", name, self.file_suffix())
+  }
+}
+
+/// Element type of the session input `name`, `f32` when the session does not declare one.
+pub fn input_type(
+  session: &Session,
+  name: &str,
+) -> TensorElementType {
+  session
+    .inputs
+    .iter()
+    .find(|input| input.name == name)
+    .and_then(|input| input.input_type.tensor_type())
+    .unwrap_or(TensorElementType::Float32)
+}
+
+/// Builds a tensor of `element_type` from `f32` values, converting them for half precision inputs.
+pub fn tensor_from_f32<D: Dimension + 'static>(
+  array: Array<f32, D>,
+  element_type: TensorElementType,
+) -> Result<DynValue> {
+  match element_type {
+    TensorElementType::Float32 => Ok(Tensor::from_array(array)?.into_dyn()),
+    TensorElementType::Float16 => Ok(Tensor::from_array(array.mapv(f16::from_f32))?.into_dyn()),
+    other => Err(anyhow!("unsupported input element type {:?}", other)),
+  }
+}
+
+/// Copies a floating point tensor into an `f32` array, whatever its precision.
+pub fn extract_f32(value: &DynValue) -> Result<ArrayD<f32>> {
+  match value.dtype().tensor_type() {
+    Some(TensorElementType::Float16) => Ok(
+      value
+        .try_extract_array::<f16>()?
+        .mapv(f16::to_f32),
+    ),
+    _ => Ok(value.try_extract_array::<f32>()?.to_owned()),
+  }
+}
+
+/// Picks the given rows along the batch axis, keeping the precision of the tensor.
+pub fn select_batch(
+  value: &DynValue,
+  indices: &[usize],
+) -> Result<DynValue> {
+  match value.dtype().tensor_type() {
+    Some(TensorElementType::Float16) => {
+      let array = value.try_extract_array::<f16>()?;
+      Ok(Tensor::from_array(array.select(Axis(0), indices))?.into_dyn())
+    },
+    _ => {
+      let array = value.try_extract_array::<f32>()?;
+      Ok(Tensor::from_array(array.select(Axis(0), indices))?.into_dyn())
+    },
+  }
+}
+
+/// Size of the batch axis of a tensor.
+pub fn batch_size(value: &DynValue) -> Option<usize> {
+  match value.dtype() {
+    ValueType::Tensor { shape, .. } => shape
+      .first()
+      .and_then(|&size| usize::try_from(size).ok()),
+    _ => None,
+  }
+}
@@ -0,0 +1,287 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use super::{
+  alignment::LANGUAGES_WITHOUT_SPACES,
+  whisper::{AUTO_LANGUAGE, GenerationConfig, TranscriptionResult, WhisperPipeline},
+  whisper_processor::SAMPLE_RATE,
+};
+use crate::helpers::audio::StreamConverter;
+
+/// Once the buffer holds more audio than this, it is trimmed at the end of the last committed segment.
+const MAX_BUFFER_SECONDS: f32 = 15.0;
+/// Most committed words before the buffer that prompt its decodes.
+const MAX_PREVIOUS_WORDS: usize = 64;
+
+#[derive(Debug, Clone)]
+struct StreamingWord {
+  text:  String,
+  start: f32,
+  end:   f32,
+}
+
+/// The transcript of a stream after a decode: the committed text, which will not change anymore,
+/// followed by the tentative text that may still be revised by the next decode.
+#[derive(Serialize, specta::Type, Clone, Debug)]
+pub struct StreamingPartial {
+  pub session_id: u32,
+  pub committed:  String,
+  pub tentative:  String,
+}
+
+/// Text that was just committed, with the time span it covers in seconds from the start of the stream.
+#[derive(Serialize, specta::Type, Clone, Debug)]
+pub struct StreamingFinal {
+  pub session_id: u32,
+  pub text:       String,
+  pub start:      f32,
+  pub end:        f32,
+}
+
+pub struct StreamingUpdate {
+  pub partial:   StreamingPartial,
+  /// Present when the decode committed new words.
+  pub committed: Option<StreamingFinal>,
+}
+
+/// Incremental transcription of an audio stream.
+///
+/// Every push re-decodes a rolling buffer of the most recent audio. Words are committed with the
+/// local agreement policy: a word is final once two consecutive decodes agree on it and on
+/// everything before it. The buffer is trimmed at segment boundaries that are fully committed, so
+/// decoding cost stays bounded however long the stream runs.
+pub struct StreamingSession {
+  id:                  u32,
+  gen_config:          GenerationConfig,
+  buffer:              Vec<f32>,
+  /// Seconds of audio already dropped from the front of the buffer.
+  buffer_offset:       f32,
+  committed:           Vec<StreamingWord>,
+  /// Number of committed words that were decoded from the audio still in the buffer.
+  committed_in_buffer: usize,
+  tentative:           Vec<StreamingWord>,
+  separator:           &'static str,
+  /// Converts the pushed audio to 16kHz mono, unless it is already.
+  converter:           Option<StreamConverter>,
+}
+
+impl StreamingSession {
+  pub fn new(
+    id: u32,
+    gen_config: GenerationConfig,
+  ) -> Self {
+    Self {
+      id,
+      gen_config: GenerationConfig {
+        // Segment boundaries are where the buffer gets trimmed.
+        return_timestamps: true,
+        // The buffer is re-decoded on every push, its own text must not become its prompt.
+        condition_on_previous_text: false,
+        ..gen_config
+      },
+      buffer: Vec::new(),
+      buffer_offset: 0.0,
+      committed: Vec::new(),
+      committed_in_buffer: 0,
+      tentative: Vec::new(),
+      separator: " ",
+      converter: None,
+    }
+  }
+
+  /// Sets the format of the audio pushed from now on, interleaved with `channels` channels at
+  /// `sample_rate` Hz.
+  pub fn set_input_format(
+    &mut self,
+    sample_rate: usize,
+    channels: usize,
+  ) -> Result<()> {
+    self.converter = if sample_rate == SAMPLE_RATE && channels == 1 {
+      None
+    } else {
+      Some(StreamConverter::new(sample_rate, channels, SAMPLE_RATE)?)
+    };
+    Ok(())
+  }
+
+  /// Appends audio in the input format of the session (mono, 16kHz unless set otherwise) to the
+  /// buffer and re-decodes it.
+  pub fn push_audio(
+    &mut self,
+    pipeline: &mut WhisperPipeline,
+    audio: &[f32],
+  ) -> Result<StreamingUpdate> {
+    match &mut self.converter {
+      Some(converter) => self.buffer.extend(converter.push(audio)?),
+      None => self.buffer.extend_from_slice(audio),
+    }
+    // The text committed from the audio trimmed off the buffer leads into it.
+    let trimmed = self.committed.len() - self.committed_in_buffer;
+    if trimmed > 0 {
+      let previous = &self.committed[trimmed.saturating_sub(MAX_PREVIOUS_WORDS)..trimmed];
+      self.gen_config.previous_text = Some(self.join(previous));
+    }
+    let result = pipeline.transcribe_with_timestamps(&self.buffer, &self.gen_config)?;
+
+    // Stick to the first detected language, so that the hypotheses stay comparable.
+    if let Some(language) = &result.language {
+      if matches!(
+        self.gen_config.language.as_deref(),
+        None | Some(AUTO_LANGUAGE)
+      ) {
+        self.gen_config.language = Some(language.clone());
+      }
+      if LANGUAGES_WITHOUT_SPACES.contains(&language.as_str()) {
+        self.separator = "";
+      }
+    }
+
+    let segments = self.segment_words(&result);
+    let hypothesis: Vec<StreamingWord> = segments
+      .iter()
+      .flatten()
+      .skip(self.committed_in_buffer)
+      .cloned()
+      .collect();
+
+    let agreed = self
+      .tentative
+      .iter()
+      .zip(&hypothesis)
+      .take_while(|(previous, current)| previous.text == current.text)
+      .count();
+
+    let newly_committed = &hypothesis[..agreed];
+    let committed = match (newly_committed.first(), newly_committed.last()) {
+      (Some(first), Some(last)) => Some(StreamingFinal {
+        session_id: self.id,
+        text:       self.join(newly_committed),
+        start:      first.start,
+        end:        last.end,
+      }),
+      _ => None,
+    };
+
+    self.committed.extend_from_slice(newly_committed);
+    self.committed_in_buffer += agreed;
+    self.tentative = hypothesis[agreed..].to_vec();
+    self.trim_buffer(&result, &segments);
+
+    Ok(StreamingUpdate {
+      partial: self.partial(),
+      committed,
+    })
+  }
+
+  /// Ends the stream, committing the tentative words as they are. Returns the words committed by
+  /// this call, if any, and the whole transcript.
+  pub fn finish(&mut self) -> (Option<StreamingFinal>, String) {
+    let remaining = std::mem::take(&mut self.tentative);
+    let committed = match (remaining.first(), remaining.last()) {
+      (Some(first), Some(last)) => Some(StreamingFinal {
+        session_id: self.id,
+        text:       self.join(&remaining),
+        start:      first.start,
+        end:        last.end,
+      }),
+      _ => None,
+    };
+    self.committed.extend(remaining);
+
+    (committed, self.join(&self.committed))
+  }
+
+  fn partial(&self) -> StreamingPartial {
+    StreamingPartial {
+      session_id: self.id,
+      committed:  self.join(&self.committed),
+      tentative:  self.join(&self.tentative),
+    }
+  }
+
+  fn join(
+    &self,
+    words: &[StreamingWord],
+  ) -> String {
+    words
+      .iter()
+      .map(|word| word.text.as_str())
+      .collect::<Vec<_>>()
+      .join(self.separator)
+  }
+
+  /// Splits every segment into words with times relative to the start of the stream. Without word
+  /// timestamps, every word gets the time span of its segment.
+  fn segment_words(
+    &self,
+    result: &TranscriptionResult,
+  ) -> Vec<Vec<StreamingWord>> {
+    result
+      .segments
+      .iter()
+      .map(|segment| match &segment.words {
+        Some(words) => words
+          .iter()
+          .map(|word| StreamingWord {
+            text:  word.word.clone(),
+            start: self.buffer_offset + word.start,
+            end:   self.buffer_offset + word.end,
+          })
+          .collect(),
+        None => {
+          let texts: Vec<String> = if self.separator.is_empty() {
+            segment
+              .text
+              .chars()
+              .filter(|c| !c.is_whitespace())
+              .map(String::from)
+              .collect()
+          } else {
+            segment
+              .text
+              .split_whitespace()
+              .map(String::from)
+              .collect()
+          };
+          texts
+            .into_iter()
+            .map(|text| StreamingWord {
+              text,
+              start: self.buffer_offset + segment.start,
+              end: self.buffer_offset + segment.end,
+            })
+            .collect()
+        },
+      })
+      .collect()
+  }
+
+  /// Drops the audio of the leading segments whose words are all committed, once the buffer is
+  /// longer than [`MAX_BUFFER_SECONDS`].
+  fn trim_buffer(
+    &mut self,
+    result: &TranscriptionResult,
+    segments: &[Vec<StreamingWord>],
+  ) {
+    if (self.buffer.len() as f32) < MAX_BUFFER_SECONDS * SAMPLE_RATE as f32 {
+      return;
+    }
+
+    let mut words = 0;
+    let mut cut = None;
+    for (segment, segment_words) in result.segments.iter().zip(segments) {
+      if words + segment_words.len() > self.committed_in_buffer {
+        break;
+      }
+      words += segment_words.len();
+      cut = Some((segment.end, words));
+    }
+
+    if let Some((end, words)) = cut {
+      let samples = ((end * SAMPLE_RATE as f32) as usize).min(self.buffer.len());
+      self.buffer.drain(..samples);
+      self.buffer_offset += samples as f32 / SAMPLE_RATE as f32;
+      self.committed_in_buffer -= words;
+    }
+  }
+}
@@ -0,0 +1,243 @@
+use super::whisper_processor::{HOP_LENGTH, SAMPLE_RATE};
+
+/// Samples covered by one timestamp step, i.e. two mel frames (the encoder's input stride).
+pub const SAMPLES_PER_TIMESTAMP: usize = 2 * HOP_LENGTH;
+/// Seconds covered by one timestamp step (0.02 s).
+pub const TIME_PRECISION: f32 = SAMPLES_PER_TIMESTAMP as f32 / SAMPLE_RATE as f32;
+
+use super::whisper::TranscriptionWord;
+
+/// A run of generated tokens together with the time span it covers, in seconds from the start of
+/// the whole input. The tokens still contain the surrounding timestamp tokens.
+#[derive(Debug, Clone)]
+pub struct TimestampedSegment {
+  pub start:          f32,
+  pub end:            f32,
+  pub tokens:         Vec<i64>,
+  pub token_logprobs: Vec<f32>,
+  pub no_speech_prob: Option<f32>,
+  pub words:          Option<Vec<TranscriptionWord>>,
+}
+
+/// The segments decoded from one 30 second window, and how far the next window should seek.
+#[derive(Debug)]
+pub struct WindowSegments {
+  pub segments:         Vec<TimestampedSegment>,
+  pub consumed_samples: usize,
+}
+
+impl WindowSegments {
+  /// Treats the window as a single segment and consumes it entirely, used when decoding without timestamps.
+  pub fn whole(
+    tokens: Vec<i64>,
+    time_offset: f32,
+    window_samples: usize,
+  ) -> Self {
+    Self {
+      segments:         vec![TimestampedSegment {
+        start: time_offset,
+        end: time_offset + window_samples as f32 / SAMPLE_RATE as f32,
+        tokens,
+        token_logprobs: Vec::new(),
+        no_speech_prob: None,
+        words: None,
+      }],
+      consumed_samples: window_samples,
+    }
+  }
+}
+
+/// Splits the tokens generated for one window into segments at each pair of consecutive timestamp
+/// tokens, following the reference long-form algorithm.
+///
+/// When the window ends with an unfinished segment (the output was cut off, or the speech continues
+/// past the window), that segment is dropped and the next window starts at the last complete
+/// timestamp instead, so it gets decoded again with its full context.
+pub fn split_window(
+  tokens: &[i64],
+  timestamp_begin: i64,
+  time_offset: f32,
+  window_samples: usize,
+) -> WindowSegments {
+  let is_timestamp = |token: &i64| *token >= timestamp_begin;
+  let to_seconds = |token: i64| (token - timestamp_begin) as f32 * TIME_PRECISION;
+
+  let single_timestamp_ending = tokens.len() >= 2
+    && !is_timestamp(&tokens[tokens.len() - 2])
+    && is_timestamp(&tokens[tokens.len() - 1]);
+
+  let mut slices: Vec<usize> = (1..tokens.len())
+    .filter(|&i| is_timestamp(&tokens[i - 1]) && is_timestamp(&tokens[i]))
+    .collect();
+
+  if slices.is_empty() {
+    let end = tokens
+      .iter()
+      .rev()
+      .find(|token| is_timestamp(token))
+      .filter(|&&token| token != timestamp_begin)
+      .map_or(window_samples as f32 / SAMPLE_RATE as f32, |&token| {
+        to_seconds(token)
+      });
+
+    return WindowSegments {
+      segments:         vec![TimestampedSegment {
+        start:          time_offset,
+        end:            time_offset + end,
+        tokens:         tokens.to_vec(),
+        token_logprobs: Vec::new(),
+        no_speech_prob: None,
+        words:          None,
+      }],
+      consumed_samples: window_samples,
+    };
+  }
+
+  if single_timestamp_ending {
+    slices.push(tokens.len());
+  }
+
+  let mut segments = Vec::with_capacity(slices.len());
+  let mut last_slice = 0;
+  for &current_slice in &slices {
+    let sliced = &tokens[last_slice..current_slice];
+    let start = sliced
+      .first()
+      .copied()
+      .filter(is_timestamp)
+      .map_or(0.0, to_seconds);
+    let end = sliced
+      .last()
+      .copied()
+      .filter(is_timestamp)
+      .map_or(start, to_seconds);
+
+    segments.push(TimestampedSegment {
+      start:          time_offset + start,
+      end:            time_offset + end,
+      tokens:         sliced.to_vec(),
+      token_logprobs: Vec::new(),
+      no_speech_prob: None,
+      words:          None,
+    });
+    last_slice = current_slice;
+  }
+
+  let consumed_samples = if single_timestamp_ending {
+    window_samples
+  } else {
+    // Seek to the last timestamp of the last complete segment.
+    let last_timestamp = usize::try_from(tokens[last_slice - 1] - timestamp_begin).unwrap_or(0);
+    match last_timestamp * SAMPLES_PER_TIMESTAMP {
+      0 => window_samples,
+      samples => samples.min(window_samples),
+    }
+  };
+
+  WindowSegments {
+    segments,
+    consumed_samples,
+  }
+}
+
+/// Same as [`split_window`], but keeps an unfinished segment at the end of the window as a last
+/// segment ending with the window, for windows decoded independently of each other where no next
+/// window picks it up again.
+pub fn split_window_keeping_unfinished(
+  tokens: &[i64],
+  timestamp_begin: i64,
+  time_offset: f32,
+  window_samples: usize,
+) -> WindowSegments {
+  let mut window_segments = split_window(tokens, timestamp_begin, time_offset, window_samples);
+
+  let split_tokens: usize = window_segments
+    .segments
+    .iter()
+    .map(|segment| segment.tokens.len())
+    .sum();
+  if split_tokens < tokens.len() {
+    let start = window_segments
+      .segments
+      .last()
+      .map_or(time_offset, |segment| segment.end);
+    window_segments.segments.push(TimestampedSegment {
+      start,
+      end: time_offset + window_samples as f32 / SAMPLE_RATE as f32,
+      tokens: tokens[split_tokens..].to_vec(),
+      token_logprobs: Vec::new(),
+      no_speech_prob: None,
+      words: None,
+    });
+  }
+  window_segments.consumed_samples = window_samples;
+
+  window_segments
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TIMESTAMP_BEGIN: i64 = 100;
+  const WINDOW_SAMPLES: usize = 30 * SAMPLE_RATE;
+
+  fn assert_span(
+    segment: &TimestampedSegment,
+    start: f32,
+    end: f32,
+  ) {
+    assert!(
+      (segment.start - start).abs() < 1e-4,
+      "start {} != {}",
+      segment.start,
+      start
+    );
+    assert!(
+      (segment.end - end).abs() < 1e-4,
+      "end {} != {}",
+      segment.end,
+      end
+    );
+  }
+
+  #[test]
+  fn split_window_drops_an_unfinished_trailing_segment() {
+    // <|0.00|> a b <|1.00|><|1.00|> c d <|2.00|><|2.00|> e f, where "e f" never gets its end.
+    let tokens = [100, 1, 2, 150, 150, 3, 4, 200, 200, 5, 6];
+
+    let window = split_window(&tokens, TIMESTAMP_BEGIN, 10.0, WINDOW_SAMPLES);
+
+    assert_eq!(window.segments.len(), 2);
+    assert_eq!(window.segments[0].tokens, vec![100, 1, 2, 150]);
+    assert_span(&window.segments[0], 10.0, 11.0);
+    assert_eq!(window.segments[1].tokens, vec![150, 3, 4, 200]);
+    assert_span(&window.segments[1], 11.0, 12.0);
+    // The next window starts again at the last complete timestamp.
+    assert_eq!(window.consumed_samples, 100 * SAMPLES_PER_TIMESTAMP);
+  }
+
+  #[test]
+  fn split_window_consumes_the_window_after_a_single_timestamp_ending() {
+    let tokens = [100, 1, 2, 150, 150, 3, 4, 200];
+
+    let window = split_window(&tokens, TIMESTAMP_BEGIN, 0.0, WINDOW_SAMPLES);
+
+    assert_eq!(window.segments.len(), 2);
+    assert_eq!(window.segments[1].tokens, vec![150, 3, 4, 200]);
+    assert_span(&window.segments[1], 1.0, 2.0);
+    assert_eq!(window.consumed_samples, WINDOW_SAMPLES);
+  }
+
+  #[test]
+  fn split_window_keeping_unfinished_ends_the_trailing_segment_with_the_window() {
+    let tokens = [100, 1, 2, 150, 150, 3, 4, 200, 200, 5, 6];
+
+    let window = split_window_keeping_unfinished(&tokens, TIMESTAMP_BEGIN, 10.0, WINDOW_SAMPLES);
+
+    assert_eq!(window.segments.len(), 3);
+    assert_eq!(window.segments[2].tokens, vec![200, 5, 6]);
+    assert_span(&window.segments[2], 12.0, 40.0);
+    assert_eq!(window.consumed_samples, WINDOW_SAMPLES);
+  }
+}
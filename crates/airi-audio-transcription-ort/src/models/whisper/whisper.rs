@@ -0,0 +1,2069 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use airi_ort_common::{
+  huggingface::ProgressEmitter,
+  model_source::{ModelFiles, ModelSource},
+  optimized_model_cache::OptimizedModelCache,
+  session_options::SessionOptions,
+  worker::CancellationToken,
+};
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use log::{info, warn};
+use ndarray::{Array2, Array3, ArrayView1, ArrayView3, Axis, s};
+use ort::{session::Session, value::DynValue};
+use serde::{Deserialize, Serialize};
+use tokenizers::Tokenizer;
+
+use super::{
+  alignment,
+  decoder::{WhisperDecoder, repeat_batch},
+  decoding::{self, BeamSearch, Hypothesis},
+  logits_processor::{LogitsProcessorChain, SuppressBlank, SuppressTokens, TimestampRules},
+  precision::{ModelPrecision, batch_size, input_type, select_batch, tensor_from_f32},
+  timestamps::{
+    TIME_PRECISION,
+    TimestampedSegment,
+    WindowSegments,
+    split_window,
+    split_window_keeping_unfinished,
+  },
+  whisper_processor::{HOP_LENGTH, N_SAMPLES, SAMPLE_RATE, WhisperProcessor},
+};
+
+// Helper function to provide a default value of true for serde
+const fn default_true() -> bool {
+  true
+}
+
+/// Vocabulary size of the English-only checkpoints, which lack the language tokens.
+const ENGLISH_ONLY_VOCAB_SIZE: usize = 51864;
+
+#[derive(Deserialize, Debug)]
+pub struct WhisperConfig {
+  pub num_mel_bins:            i64,
+  pub decoder_start_token_id:  i64,
+  pub eos_token_id:            i64,
+  pub decoder_layers:          usize,
+  pub decoder_attention_heads: usize,
+  pub d_model:                 usize,
+  // Corrected: Use serde default for missing is_multilingual field
+  #[serde(default = "default_true")]
+  pub is_multilingual:         bool,
+  #[serde(default)]
+  pub vocab_size:              Option<usize>,
+  #[serde(default)]
+  pub no_timestamps_token_id:  Option<i64>,
+  #[serde(default)]
+  pub lang_to_id:              HashMap<String, i64>,
+  /// `<|transcribe|>` and `<|translate|>`, read from the tokenizer config.
+  #[serde(default)]
+  pub transcribe_token_id:     Option<i64>,
+  #[serde(default)]
+  pub translate_token_id:      Option<i64>,
+  /// Pairs of decoder layer and head whose cross attention follows the audio, used for word timestamps.
+  #[serde(default)]
+  pub alignment_heads:         Vec<(usize, usize)>,
+  /// Non-speech tokens (symbols, speaker tags, ...) that are never generated.
+  #[serde(default)]
+  pub suppress_tokens:         Vec<i64>,
+  /// Tokens the output may not start with, usually the blank and the end of text.
+  #[serde(default)]
+  pub begin_suppress_tokens:   Vec<i64>,
+  /// `<|nospeech|>` (`<|nocaptions|>` in older vocabularies), read from the tokenizer config.
+  #[serde(default)]
+  pub no_speech_token_id:      Option<i64>,
+}
+
+/// Fields of `generation_config.json` that are not part of `config.json`.
+#[derive(Deserialize, Debug, Default)]
+struct GenerationConfigFile {
+  #[serde(default)]
+  is_multilingual:        Option<bool>,
+  #[serde(default)]
+  no_timestamps_token_id: Option<i64>,
+  #[serde(default)]
+  alignment_heads:        Vec<(usize, usize)>,
+  #[serde(default)]
+  lang_to_id:             HashMap<String, i64>,
+  #[serde(default)]
+  suppress_tokens:        Vec<i64>,
+  #[serde(default)]
+  begin_suppress_tokens:  Vec<i64>,
+}
+
+/// Files every Whisper export needs, besides the encoder (`onnx/encoder_model.onnx`) and a decoder
+/// (`onnx/decoder_model_merged.onnx`, or `onnx/decoder_model.onnx` together with
+/// `onnx/decoder_with_past_model.onnx`), all of them suffixed by the [`ModelPrecision`].
+pub const REQUIRED_FILES: &[&str] = &["config.json", "tokenizer.json", "tokenizer_config.json"];
+
+/// Most tokens of previous text put in front of the prompt, half of the decoder context minus
+/// `<|startofprev|>`, as in the reference implementation.
+const MAX_PROMPT_TOKENS: usize = 223;
+
+/// Temperature above which the previous text is no longer used as a prompt, a window that needed
+/// such a high temperature is likely to be garbage.
+const PROMPT_RESET_TEMPERATURE: f32 = 0.5;
+
+/// Language value that asks the model to detect the spoken language.
+pub const AUTO_LANGUAGE: &str = "auto";
+
+/// What the decoder is asked to produce.
+#[derive(
+  Deserialize, Serialize, specta::Type, ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Task {
+  /// Text in the spoken language.
+  #[default]
+  Transcribe,
+  /// English text, whatever the spoken language.
+  Translate,
+}
+
+#[derive(Debug)]
+pub struct GenerationConfig {
+  /// Language code or name, `None` or [`AUTO_LANGUAGE`] to detect it.
+  pub language:                    Option<String>,
+  pub task:                        Task,
+  pub return_timestamps:           bool,
+  /// Align every word to the audio using the cross attention of the alignment heads.
+  pub return_word_timestamps:      bool,
+  pub max_new_tokens:              usize,
+  /// Number of beams kept by beam search at temperature zero, `1` decodes greedily.
+  pub beam_size:                   usize,
+  /// Beam search stops once `beam_size * patience` hypotheses have finished.
+  pub patience:                    f32,
+  /// Exponent of the length penalty used to rank finished beams, `None` divides by the length.
+  pub length_penalty:              Option<f32>,
+  /// Temperatures tried in order, moving to the next one while the output fails the thresholds below.
+  pub temperatures:                Vec<f32>,
+  /// Retry at a higher temperature when the text compresses better than this (a repetition loop).
+  pub compression_ratio_threshold: Option<f32>,
+  /// Retry at a higher temperature when the average token log-probability is below this.
+  pub logprob_threshold:           Option<f32>,
+  /// Text put after `<|startofprev|>` before the first window, e.g. to bias the spelling of names.
+  pub initial_prompt:              Option<String>,
+  /// Prompt every window with the text transcribed before it in the same transcription, instead of
+  /// only prompting the first window with `initial_prompt` and `previous_text`.
+  pub condition_on_previous_text:  bool,
+  /// Text transcribed right before this audio, e.g. the result of the previous chunk of the same
+  /// recording, put after `initial_prompt` before the first window. Nothing else carries over from
+  /// one transcription to the next.
+  pub previous_text:               Option<String>,
+  /// Token ids never generated, on top of the non-speech tokens listed by the model.
+  pub suppress_tokens:             Vec<i64>,
+  /// Keep the output from starting with a blank or ending right away.
+  pub suppress_blank:              bool,
+  /// Latest start of the first segment in seconds, `None` leaves it unbounded.
+  pub max_initial_timestamp:       Option<f32>,
+  /// A window is treated as silence, and its output dropped, when the probability of
+  /// `<|nospeech|>` is above this and the average log-probability is below `logprob_threshold`.
+  pub no_speech_threshold:         Option<f32>,
+  /// Decoding stops once the text ends with an n-gram repeated this many times in a row, and the
+  /// repeats are dropped. Short n-grams need to repeat more often to count as a loop.
+  pub repetition_threshold:        Option<usize>,
+  /// Most windows encoded and decoded together by [`WhisperPipeline::transcribe_batch`].
+  pub batch_size:                  usize,
+}
+
+impl Default for GenerationConfig {
+  fn default() -> Self {
+    Self {
+      language:                    Some("en".to_string()),
+      task:                        Task::Transcribe,
+      return_timestamps:           true,
+      return_word_timestamps:      false,
+      max_new_tokens:              128,
+      beam_size:                   1,
+      patience:                    1.0,
+      length_penalty:              None,
+      temperatures:                vec![0.0, 0.2, 0.4, 0.6, 0.8, 1.0],
+      compression_ratio_threshold: Some(2.4),
+      logprob_threshold:           Some(-1.0),
+      initial_prompt:              None,
+      condition_on_previous_text:  false,
+      previous_text:               None,
+      suppress_tokens:             Vec::new(),
+      suppress_blank:              true,
+      max_initial_timestamp:       Some(1.0),
+      no_speech_threshold:         Some(0.6),
+      repetition_threshold:        Some(3),
+      batch_size:                  8,
+    }
+  }
+}
+
+/// Generation settings that can be set from the frontend, missing fields keep the values given
+/// by the command arguments or the defaults of [`GenerationConfig`].
+#[derive(Deserialize, specta::Type, Debug, Default)]
+pub struct TranscriptionOptions {
+  /// Language code or name, or [`AUTO_LANGUAGE`] to detect it.
+  #[specta(optional)]
+  pub language:                    Option<String>,
+  #[specta(optional)]
+  pub task:                        Option<Task>,
+  #[specta(optional)]
+  pub return_timestamps:           Option<bool>,
+  #[specta(optional)]
+  pub return_word_timestamps:      Option<bool>,
+  #[specta(optional)]
+  pub max_new_tokens:              Option<usize>,
+  #[specta(optional)]
+  pub beam_size:                   Option<usize>,
+  #[specta(optional)]
+  pub patience:                    Option<f32>,
+  #[specta(optional)]
+  pub length_penalty:              Option<f32>,
+  #[specta(optional)]
+  pub temperatures:                Option<Vec<f32>>,
+  #[specta(optional)]
+  pub compression_ratio_threshold: Option<f32>,
+  #[specta(optional)]
+  pub logprob_threshold:           Option<f32>,
+  #[specta(optional)]
+  pub initial_prompt:              Option<String>,
+  #[specta(optional)]
+  pub condition_on_previous_text:  Option<bool>,
+  #[specta(optional)]
+  pub previous_text:               Option<String>,
+  #[specta(optional)]
+  pub suppress_tokens:             Option<Vec<i64>>,
+  #[specta(optional)]
+  pub suppress_blank:              Option<bool>,
+  #[specta(optional)]
+  pub max_initial_timestamp:       Option<f32>,
+  #[specta(optional)]
+  pub no_speech_threshold:         Option<f32>,
+  #[specta(optional)]
+  pub repetition_threshold:        Option<usize>,
+  #[specta(optional)]
+  pub batch_size:                  Option<usize>,
+}
+
+impl TranscriptionOptions {
+  pub fn apply(
+    self,
+    gen_config: &mut GenerationConfig,
+  ) {
+    if self.language.is_some() {
+      gen_config.language = self.language;
+    }
+    if let Some(task) = self.task {
+      gen_config.task = task;
+    }
+    if let Some(return_timestamps) = self.return_timestamps {
+      gen_config.return_timestamps = return_timestamps;
+    }
+    if let Some(return_word_timestamps) = self.return_word_timestamps {
+      gen_config.return_word_timestamps = return_word_timestamps;
+    }
+    if let Some(max_new_tokens) = self.max_new_tokens {
+      gen_config.max_new_tokens = max_new_tokens;
+    }
+    if let Some(beam_size) = self.beam_size {
+      gen_config.beam_size = beam_size;
+    }
+    if let Some(patience) = self.patience {
+      gen_config.patience = patience;
+    }
+    if self.length_penalty.is_some() {
+      gen_config.length_penalty = self.length_penalty;
+    }
+    if let Some(temperatures) = self.temperatures {
+      gen_config.temperatures = temperatures;
+    }
+    if self.compression_ratio_threshold.is_some() {
+      gen_config.compression_ratio_threshold = self.compression_ratio_threshold;
+    }
+    if self.logprob_threshold.is_some() {
+      gen_config.logprob_threshold = self.logprob_threshold;
+    }
+    if self.initial_prompt.is_some() {
+      gen_config.initial_prompt = self.initial_prompt;
+    }
+    if let Some(condition_on_previous_text) = self.condition_on_previous_text {
+      gen_config.condition_on_previous_text = condition_on_previous_text;
+    }
+    if self.previous_text.is_some() {
+      gen_config.previous_text = self.previous_text;
+    }
+    if let Some(suppress_tokens) = self.suppress_tokens {
+      gen_config.suppress_tokens = suppress_tokens;
+    }
+    if let Some(suppress_blank) = self.suppress_blank {
+      gen_config.suppress_blank = suppress_blank;
+    }
+    if self.max_initial_timestamp.is_some() {
+      gen_config.max_initial_timestamp = self.max_initial_timestamp;
+    }
+    if self.no_speech_threshold.is_some() {
+      gen_config.no_speech_threshold = self.no_speech_threshold;
+    }
+    if self.repetition_threshold.is_some() {
+      gen_config.repetition_threshold = self.repetition_threshold;
+    }
+    if let Some(batch_size) = self.batch_size {
+      gen_config.batch_size = batch_size;
+    }
+  }
+}
+
+#[derive(Serialize, specta::Type, Clone, Debug)]
+pub struct LanguageProbability {
+  pub language:    String,
+  pub probability: f32,
+}
+
+pub struct GenerationOutput {
+  /// Generated tokens, without the prompt and the end of text token.
+  pub tokens:           Vec<i64>,
+  /// Number of prompt tokens fed to the decoder before the generated ones.
+  pub prompt_length:    usize,
+  /// Number of those prompt tokens that are previous text (`<|startofprev|>` included), placed
+  /// before `<|startoftranscript|>`.
+  pub context_length:   usize,
+  /// Language code the output was decoded in, either requested or detected.
+  pub language:         Option<String>,
+  /// Log-probability of every token of `tokens`.
+  pub token_logprobs:   Vec<f32>,
+  /// Sum of the token log-probabilities divided by the number of tokens plus one.
+  pub avg_logprob:      f32,
+  /// Temperature the output was decoded at.
+  pub temperature:      f32,
+  /// Probability of `<|nospeech|>` at the `<|startoftranscript|>` position, when the vocabulary
+  /// has that token.
+  pub no_speech_prob:   Option<f32>,
+  /// The decoder was stopped on a repetition loop, whose repeats were dropped from `tokens`.
+  pub repetition:       bool,
+  /// Cross-attention weights of the alignment heads for every decoder position,
+  /// shaped `[heads, positions, encoder_length]`, when word timestamps were requested.
+  pub cross_attentions: Option<Array3<f32>>,
+}
+
+/// What a single decoding strategy produced for one window.
+struct DecodedSequence {
+  hypothesis:       Hypothesis,
+  cross_attentions: Option<Array3<f32>>,
+  no_speech_prob:   Option<f32>,
+  repetition:       bool,
+}
+
+impl GenerationOutput {
+  fn exceeds_no_speech_threshold(
+    &self,
+    gen_config: &GenerationConfig,
+  ) -> bool {
+    matches!(
+      (self.no_speech_prob, gen_config.no_speech_threshold),
+      (Some(no_speech_prob), Some(threshold)) if no_speech_prob > threshold
+    )
+  }
+}
+
+static WHISPER_LANGUAGES: std::sync::LazyLock<HashMap<&'static str, &'static str>> =
+  std::sync::LazyLock::new(|| {
+    let mut m = HashMap::new();
+    m.insert("en", "english");
+    m.insert("zh", "chinese");
+    m.insert("de", "german");
+    m.insert("es", "spanish");
+    m.insert("ru", "russian");
+    m.insert("ko", "korean");
+    m.insert("fr", "french");
+    m.insert("ja", "japanese");
+    m.insert("pt", "portuguese");
+    m.insert("tr", "turkish");
+    m.insert("pl", "polish");
+    m.insert("ca", "catalan");
+    m.insert("nl", "dutch");
+    m.insert("ar", "arabic");
+    m.insert("sv", "swedish");
+    m.insert("it", "italian");
+    m.insert("id", "indonesian");
+    m.insert("hi", "hindi");
+    m.insert("fi", "finnish");
+    m.insert("vi", "vietnamese");
+    m.insert("he", "hebrew");
+    m.insert("uk", "ukrainian");
+    m.insert("el", "greek");
+    m.insert("ms", "malay");
+    m.insert("cs", "czech");
+    m.insert("ro", "romanian");
+    m.insert("da", "danish");
+    m.insert("hu", "hungarian");
+    m.insert("ta", "tamil");
+    m.insert("no", "norwegian");
+    m.insert("th", "thai");
+    m.insert("ur", "urdu");
+    m.insert("hr", "croatian");
+    m.insert("bg", "bulgarian");
+    m.insert("lt", "lithuanian");
+    m.insert("la", "latin");
+    m.insert("mi", "maori");
+    m.insert("ml", "malayalam");
+    m.insert("cy", "welsh");
+    m.insert("sk", "slovak");
+    m.insert("te", "telugu");
+    m.insert("fa", "persian");
+    m.insert("lv", "latvian");
+    m.insert("bn", "bengali");
+    m.insert("sr", "serbian");
+    m.insert("az", "azerbaijani");
+    m.insert("sl", "slovenian");
+    m.insert("kn", "kannada");
+    m.insert("et", "estonian");
+    m.insert("mk", "macedonian");
+    m.insert("br", "breton");
+    m.insert("eu", "basque");
+    m.insert("is", "icelandic");
+    m.insert("hy", "armenian");
+    m.insert("ne", "nepali");
+    m.insert("mn", "mongolian");
+    m.insert("bs", "bosnian");
+    m.insert("kk", "kazakh");
+    m.insert("sq", "albanian");
+    m.insert("sw", "swahili");
+    m.insert("gl", "galician");
+    m.insert("mr", "marathi");
+    m.insert("pa", "punjabi");
+    m.insert("si", "sinhala");
+    m.insert("km", "khmer");
+    m.insert("sn", "shona");
+    m.insert("yo", "yoruba");
+    m.insert("so", "somali");
+    m.insert("af", "afrikaans");
+    m.insert("oc", "occitan");
+    m.insert("ka", "georgian");
+    m.insert("be", "belarusian");
+    m.insert("tg", "tajik");
+    m.insert("sd", "sindhi");
+    m.insert("gu", "gujarati");
+    m.insert("am", "amharic");
+    m.insert("yi", "yiddish");
+    m.insert("lo", "lao");
+    m.insert("uz", "uzbek");
+    m.insert("fo", "faroese");
+    m.insert("ht", "haitian creole");
+    m.insert("ps", "pashto");
+    m.insert("tk", "turkmen");
+    m.insert("nn", "nynorsk");
+    m.insert("mt", "maltese");
+    m.insert("sa", "sanskrit");
+    m.insert("lb", "luxembourgish");
+    m.insert("my", "myanmar");
+    m.insert("bo", "tibetan");
+    m.insert("tl", "tagalog");
+    m.insert("mg", "malagasy");
+    m.insert("as", "assamese");
+    m.insert("tt", "tatar");
+    m.insert("haw", "hawaiian");
+    m.insert("ln", "lingala");
+    m.insert("ha", "hausa");
+    m.insert("ba", "bashkir");
+    m.insert("jw", "javanese");
+    m.insert("su", "sundanese");
+    m
+  });
+
+static WHISPER_TO_LANGUAGE_CODE: std::sync::LazyLock<HashMap<&'static str, &'static str>> =
+  std::sync::LazyLock::new(|| {
+    let mut m: HashMap<&'static str, &'static str> = WHISPER_LANGUAGES
+      .iter()
+      .map(|(k, v)| (*v, *k))
+      .collect();
+    m.insert("burmese", "my");
+    m.insert("valencian", "ca");
+    m.insert("flemish", "nl");
+    m.insert("haitian", "ht");
+    m.insert("letzeburgesch", "lb");
+    m.insert("pushto", "ps");
+    m.insert("panjabi", "pa");
+    m.insert("moldavian", "ro");
+    m.insert("moldovan", "ro");
+    m.insert("sinhalese", "si");
+    m.insert("castilian", "es");
+    m
+  });
+
+pub fn whisper_language_to_code(language: &str) -> Result<String> {
+  // Accept either two-letter code (en) or full name (english).
+  let lower_lang = language.to_lowercase();
+  if let Some(&code) = WHISPER_TO_LANGUAGE_CODE.get(lower_lang.as_str()) {
+    return Ok(code.to_string());
+  }
+  if WHISPER_LANGUAGES.contains_key(lower_lang.as_str()) {
+    return Ok(lower_lang);
+  }
+  Err(anyhow!("Language '{}' is not supported.", language))
+}
+
+pub struct Whisper {
+  encoder_session: Session,
+  decoder:         WhisperDecoder,
+  config:          WhisperConfig,
+  /// Checked before every decoder step, so that a cancelled request stops mid-window.
+  cancellation:    Option<CancellationToken>,
+}
+
+impl Whisper {
+  pub fn new(
+    files: &ModelFiles,
+    precision: ModelPrecision,
+    session_options: &SessionOptions,
+    cache: &OptimizedModelCache,
+  ) -> Result<Self> {
+    let encoder_model_path = files.get(&precision.model_file("encoder_model"))?;
+    let config_path = files.get("config.json")?;
+    let tokenizer_config_path = files.get("tokenizer_config.json")?;
+
+    let mut config: WhisperConfig = serde_json::from_str(&std::fs::read_to_string(config_path)?)?;
+    let tokenizer_config: serde_json::Value =
+      serde_json::from_str(&std::fs::read_to_string(tokenizer_config_path)?)?;
+
+    if let Some(added_tokens) = tokenizer_config["added_tokens"].as_array() {
+      let lang_to_id = added_tokens
+        .iter()
+        .filter_map(|token| {
+          let content = token["content"].as_str()?;
+          let id = token["id"].as_i64()?;
+          // Language tokens, from `<|en|>` to `<|haw|>` and `<|yue|>`.
+          if content.starts_with("<|")
+            && content.ends_with("|>")
+            && content.len() >= 4
+            && content.len() <= 7
+          {
+            Some((content.to_string(), id))
+          } else {
+            None
+          }
+        })
+        .collect();
+      config.lang_to_id = lang_to_id;
+
+      let special_token = |contents: &[&str]| {
+        added_tokens.iter().find_map(|token| {
+          contents
+            .contains(&token["content"].as_str()?)
+            .then(|| token["id"].as_i64())
+            .flatten()
+        })
+      };
+      config.no_speech_token_id = special_token(&["<|nospeech|>", "<|nocaptions|>"]);
+      config.transcribe_token_id = special_token(&["<|transcribe|>"]);
+      config.translate_token_id = special_token(&["<|translate|>"]);
+    }
+
+    // The timestamp token ids are only listed in generation_config.json for most exports.
+    let generation_config_file = match files.get("generation_config.json") {
+      Ok(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+      Err(e) => {
+        warn!("generation_config.json is not available: {}", e);
+        GenerationConfigFile::default()
+      },
+    };
+    // config.json rarely says whether the model is multilingual, English-only checkpoints are
+    // told apart by their smaller vocabulary.
+    config.is_multilingual = generation_config_file
+      .is_multilingual
+      .unwrap_or(config.is_multilingual && config.vocab_size != Some(ENGLISH_ONLY_VOCAB_SIZE));
+    if config.no_timestamps_token_id.is_none() {
+      config.no_timestamps_token_id = generation_config_file.no_timestamps_token_id;
+    }
+    if config.alignment_heads.is_empty() {
+      config.alignment_heads = generation_config_file.alignment_heads;
+    }
+    if config.lang_to_id.is_empty() {
+      config.lang_to_id = generation_config_file.lang_to_id;
+    }
+    if config.suppress_tokens.is_empty() {
+      config.suppress_tokens = generation_config_file.suppress_tokens;
+    }
+    if config.begin_suppress_tokens.is_empty() {
+      config.begin_suppress_tokens = generation_config_file.begin_suppress_tokens;
+    }
+
+    let encoder_session =
+      Self::create_optimized_session(encoder_model_path, session_options, cache)?;
+
+    // Prefer the merged decoder, it holds both the first pass and the cached pass in a single graph.
+    let decoder_model_merged = precision.model_file("decoder_model_merged");
+    let decoder_model = precision.model_file("decoder_model");
+    let decoder_with_past_model = precision.model_file("decoder_with_past_model");
+    let decoder = match files.get(&decoder_model_merged) {
+      Ok(decoder_model_merged_path) => WhisperDecoder::merged(
+        Self::create_optimized_session(decoder_model_merged_path, session_options, cache)?,
+        &config,
+      )?,
+      Err(e) => {
+        warn!(
+          "Merged decoder is not available, falling back to decoder with past: {}",
+          e
+        );
+
+        files
+          .require(&[decoder_model.as_str(), decoder_with_past_model.as_str()])
+          .map_err(|missing| {
+            anyhow!(
+              "{}, a decoder pair is needed when {} is not available",
+              missing,
+              decoder_model_merged
+            )
+          })?;
+        let decoder_model_path = files.get(&decoder_model)?;
+        let decoder_with_past_model_path = files.get(&decoder_with_past_model)?;
+
+        WhisperDecoder::split(
+          Self::create_optimized_session(decoder_model_path, session_options, cache)?,
+          Self::create_optimized_session(decoder_with_past_model_path, session_options, cache)?,
+          &config,
+        )?
+      },
+    };
+
+    Ok(Self {
+      encoder_session,
+      decoder,
+      config,
+      cancellation: None,
+    })
+  }
+
+  fn create_optimized_session(
+    model_path: PathBuf,
+    session_options: &SessionOptions,
+    cache: &OptimizedModelCache,
+  ) -> Result<Session> {
+    cache.commit(&model_path, session_options)
+  }
+
+  /// The id of `<|0.00|>`, the first timestamp token; all ids above it are timestamps as well.
+  pub fn timestamp_begin(&self) -> Option<i64> {
+    self
+      .config
+      .no_timestamps_token_id
+      .map(|id| id + 1)
+  }
+
+  pub fn eos_token_id(&self) -> i64 {
+    self.config.eos_token_id
+  }
+
+  /// Stops the following decoding with an error once `cancellation` is cancelled.
+  pub fn set_cancellation_token(
+    &mut self,
+    cancellation: Option<CancellationToken>,
+  ) {
+    self.cancellation = cancellation;
+  }
+
+  fn check_cancelled(&self) -> Result<()> {
+    match &self.cancellation {
+      Some(cancellation) => cancellation.check(),
+      None => Ok(()),
+    }
+  }
+
+  /// Number of mel bins the encoder expects, 80 for most checkpoints and 128 since large-v3.
+  pub fn num_mel_bins(&self) -> Result<usize> {
+    Ok(usize::try_from(self.config.num_mel_bins)?)
+  }
+
+  /// The logits processors applied at every decoding step, in the order of the reference
+  /// implementation: blank suppression, token suppression, then the timestamp rules.
+  fn logits_processors(
+    &self,
+    gen_config: &GenerationConfig,
+  ) -> LogitsProcessorChain {
+    let mut processors = LogitsProcessorChain::default();
+
+    if gen_config.suppress_blank && !self.config.begin_suppress_tokens.is_empty() {
+      processors.push(SuppressBlank {
+        tokens: self.config.begin_suppress_tokens.clone(),
+      });
+    }
+
+    // The special tokens of the prompt are never part of the output either.
+    let mut suppress_tokens = self.config.suppress_tokens.clone();
+    suppress_tokens.extend(&gen_config.suppress_tokens);
+    suppress_tokens.push(self.config.decoder_start_token_id);
+    suppress_tokens.extend(self.config.transcribe_token_id);
+    suppress_tokens.extend(self.config.translate_token_id);
+    suppress_tokens.sort_unstable();
+    suppress_tokens.dedup();
+    processors.push(SuppressTokens {
+      tokens: suppress_tokens,
+    });
+
+    if gen_config.return_timestamps
+      && let Some(no_timestamps_token_id) = self.config.no_timestamps_token_id
+    {
+      processors.push(TimestampRules {
+        timestamp_begin: no_timestamps_token_id + 1,
+        no_timestamps_token_id,
+        eos_token_id: self.config.eos_token_id,
+        max_initial_timestamp_index: gen_config
+          .max_initial_timestamp
+          .map(|seconds| (seconds / TIME_PRECISION).round() as usize),
+      });
+    }
+
+    processors
+  }
+
+  fn retrieve_init_tokens(
+    &self,
+    context: &[i64],
+    language: Option<&str>,
+    gen_config: &GenerationConfig,
+  ) -> Result<Vec<i64>> {
+    let mut init_tokens = context.to_vec();
+    init_tokens.push(self.config.decoder_start_token_id);
+
+    // English-only models are prompted with `<|startoftranscript|>` alone.
+    if self.config.is_multilingual {
+      let lang_code = whisper_language_to_code(language.unwrap_or("en"))?;
+      let lang_token = format!("<|{lang_code}|>");
+      let lang_token_id = self
+        .config
+        .lang_to_id
+        .get(&lang_token)
+        .ok_or_else(|| anyhow!("Language token not found for: {}", lang_token))?;
+      init_tokens.push(*lang_token_id);
+
+      let (task_token_id, task_token) = match gen_config.task {
+        Task::Transcribe => (self.config.transcribe_token_id, "<|transcribe|>"),
+        Task::Translate => (self.config.translate_token_id, "<|translate|>"),
+      };
+      init_tokens.push(
+        task_token_id.ok_or_else(|| anyhow!("The tokenizer does not define {}", task_token))?,
+      );
+    } else if gen_config.task == Task::Translate {
+      return Err(anyhow!("English-only models cannot translate"));
+    }
+
+    if !gen_config.return_timestamps
+      && let Some(no_timestamps_id) = self.config.no_timestamps_token_id
+    {
+      init_tokens.push(no_timestamps_id);
+    }
+    Ok(init_tokens)
+  }
+
+  /// Runs the encoder over a batch of windows, shaped `[batch, num_mel_bins, frames]`.
+  pub fn encode(
+    &mut self,
+    input_features: ArrayView3<f32>,
+  ) -> Result<DynValue> {
+    let (batch_size, num_mel_bins, sequence_length) = input_features.dim();
+    let expected_mel_bins = usize::try_from(self.config.num_mel_bins)?;
+
+    if batch_size == 0 || num_mel_bins != expected_mel_bins {
+      return Err(anyhow!(
+        "Incorrect input feature shape. Expected [batch, {}, ...], but got [{}, {}, {}]",
+        expected_mel_bins,
+        batch_size,
+        num_mel_bins,
+        sequence_length
+      ));
+    }
+
+    // Half precision exports take `f16` features.
+    let input_type = input_type(&self.encoder_session, "input_features");
+    let owned_input = tensor_from_f32(input_features.to_owned(), input_type)?;
+    let inputs = vec![("input_features", owned_input)];
+    let mut encoder_outputs = self.encoder_session.run(inputs)?;
+    encoder_outputs
+      .remove("last_hidden_state")
+      .ok_or_else(|| anyhow!("encoder output did not contain 'last_hidden_state'"))
+  }
+
+  /// Detects the spoken language of every window of the batch, returning every language the model
+  /// knows ranked by probability.
+  pub fn detect_language(
+    &mut self,
+    input_features: ArrayView3<f32>,
+  ) -> Result<Vec<Vec<LanguageProbability>>> {
+    let encoder_hidden_states = self.encode(input_features)?;
+    self.detect_language_from_hidden_states(&encoder_hidden_states)
+  }
+
+  /// Runs a single decoder step after `<|startoftranscript|>` and reads the distribution over the
+  /// language tokens, the same way the reference implementation picks a language.
+  fn detect_language_from_hidden_states(
+    &mut self,
+    encoder_hidden_states: &DynValue,
+  ) -> Result<Vec<Vec<LanguageProbability>>> {
+    if !self.config.is_multilingual || self.config.lang_to_id.is_empty() {
+      return Err(anyhow!(
+        "Language detection is not available, the model is not multilingual"
+      ));
+    }
+
+    let batch_size = hidden_states_batch_size(encoder_hidden_states)?;
+    let input_ids = Array2::from_elem((batch_size, 1), self.config.decoder_start_token_id);
+    let output = self
+      .decoder
+      .forward(input_ids, encoder_hidden_states, None, None)?;
+
+    Ok(
+      (0..batch_size)
+        .map(|row| self.language_probabilities(output.logits.slice(s![row, -1, ..])))
+        .collect(),
+    )
+  }
+
+  /// Softmax of `logits` over the language tokens only, ranked by probability.
+  fn language_probabilities(
+    &self,
+    logits: ArrayView1<f32>,
+  ) -> Vec<LanguageProbability> {
+    let mut languages: Vec<LanguageProbability> = self
+      .config
+      .lang_to_id
+      .iter()
+      .filter_map(|(token, &id)| {
+        let logit = *logits.get(usize::try_from(id).ok()?)?;
+        let language = token
+          .trim_start_matches("<|")
+          .trim_end_matches("|>")
+          .to_string();
+        Some(LanguageProbability {
+          language,
+          probability: logit,
+        })
+      })
+      .collect();
+
+    let max_logit = languages
+      .iter()
+      .map(|language| language.probability)
+      .fold(f32::NEG_INFINITY, f32::max);
+    let mut sum = 0.0;
+    for language in &mut languages {
+      language.probability = (language.probability - max_logit).exp();
+      sum += language.probability;
+    }
+    for language in &mut languages {
+      language.probability /= sum;
+    }
+
+    languages.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+    languages
+  }
+
+  /// Resolves the language to decode every window of the batch in: the requested one, or the
+  /// detected one when the language is missing or [`AUTO_LANGUAGE`]. English-only models have no
+  /// language token.
+  pub fn resolve_languages(
+    &mut self,
+    encoder_hidden_states: &DynValue,
+    gen_config: &GenerationConfig,
+  ) -> Result<Vec<Option<String>>> {
+    let batch_size = hidden_states_batch_size(encoder_hidden_states)?;
+    if !self.config.is_multilingual {
+      return Ok(vec![None; batch_size]);
+    }
+
+    match gen_config.language.as_deref() {
+      None | Some(AUTO_LANGUAGE) => Ok(
+        self
+          .detect_language_from_hidden_states(encoder_hidden_states)?
+          .into_iter()
+          .map(|languages| {
+            languages
+              .into_iter()
+              .next()
+              .map(|detected| detected.language)
+          })
+          .collect(),
+      ),
+      Some(language) => Ok(vec![Some(whisper_language_to_code(language)?); batch_size]),
+    }
+  }
+
+  /// Decodes the encoder output once at the given temperature: with beam search when the
+  /// temperature is zero and `beam_size` is above one, otherwise greedily (temperature zero) or by
+  /// sampling.
+  ///
+  /// `context` is the previous text prompt, starting with `<|startofprev|>`, or empty.
+  pub fn decode(
+    &mut self,
+    encoder_hidden_states: &DynValue,
+    context: &[i64],
+    language: Option<String>,
+    gen_config: &GenerationConfig,
+    temperature: f32,
+  ) -> Result<GenerationOutput> {
+    self
+      .decode_batch(
+        encoder_hidden_states,
+        context,
+        vec![language],
+        gen_config,
+        temperature,
+        &mut |_, _| {},
+      )?
+      .pop()
+      .ok_or_else(|| anyhow!("decoder did not return an output"))
+  }
+
+  /// Same as [`Self::decode`] for a batch of windows, one language per window. Greedy decoding and
+  /// sampling run every window as one row of the decoder batch; beam search already fills the
+  /// batch with the beams of a single window, so windows are searched one after the other.
+  ///
+  /// `on_token` is called with the row and the id of every generated token as soon as it is
+  /// picked. Beam search only knows its tokens once the search is over, they are reported then.
+  pub fn decode_batch(
+    &mut self,
+    encoder_hidden_states: &DynValue,
+    context: &[i64],
+    languages: Vec<Option<String>>,
+    gen_config: &GenerationConfig,
+    temperature: f32,
+    on_token: &mut dyn FnMut(usize, i64),
+  ) -> Result<Vec<GenerationOutput>> {
+    let batch_size = hidden_states_batch_size(encoder_hidden_states)?;
+    if languages.len() != batch_size {
+      return Err(anyhow!(
+        "Got {} languages for a batch of {} windows",
+        languages.len(),
+        batch_size
+      ));
+    }
+
+    // Every prompt has the same layout, the language token is the only one that differs.
+    let prompts: Vec<Vec<i64>> = languages
+      .iter()
+      .map(|language| self.retrieve_init_tokens(context, language.as_deref(), gen_config))
+      .collect::<Result<_>>()?;
+    let prompt_length = prompts[0].len();
+
+    let alignment_heads = if gen_config.return_word_timestamps {
+      if self.config.alignment_heads.is_empty() {
+        return Err(anyhow!(
+          "Word timestamps are not available, the model does not define any alignment heads"
+        ));
+      }
+      Some(self.config.alignment_heads.clone())
+    } else {
+      None
+    };
+
+    let decoded = if temperature <= 0.0 && gen_config.beam_size > 1 {
+      let mut decoded = Vec::with_capacity(batch_size);
+      for (row, prompt) in prompts.iter().enumerate() {
+        let row_hidden_states = match batch_size {
+          1 => None,
+          _ => Some(select_batch(encoder_hidden_states, &[row])?),
+        };
+        let row_hidden_states = row_hidden_states
+          .as_ref()
+          .unwrap_or(encoder_hidden_states);
+
+        let mut sequence =
+          self.beam_search(row_hidden_states, prompt, context.len(), gen_config)?;
+        // Beams are reordered at every step, so the attention is collected afterwards from a single
+        // pass over the chosen sequence.
+        if let Some(alignment_heads) = alignment_heads.as_deref() {
+          sequence.cross_attentions = Some(self.alignment_cross_attentions(
+            row_hidden_states,
+            prompt,
+            &sequence.hypothesis.tokens,
+            alignment_heads,
+          )?);
+        }
+        for &token in &sequence.hypothesis.tokens {
+          on_token(row, token);
+        }
+        decoded.push(sequence);
+      }
+      decoded
+    } else {
+      let decoder_input_ids =
+        Array2::from_shape_vec((batch_size, prompt_length), prompts.concat())?;
+      self.sample(
+        encoder_hidden_states,
+        decoder_input_ids,
+        context.len(),
+        gen_config,
+        temperature,
+        alignment_heads.as_deref(),
+        on_token,
+      )?
+    };
+
+    Ok(
+      decoded
+        .into_iter()
+        .zip(languages)
+        .map(|(decoded, language)| GenerationOutput {
+          avg_logprob: decoded.hypothesis.avg_logprob(),
+          tokens: decoded.hypothesis.tokens,
+          token_logprobs: decoded.hypothesis.token_logprobs,
+          prompt_length,
+          context_length: context.len(),
+          language,
+          temperature,
+          no_speech_prob: decoded.no_speech_prob,
+          repetition: decoded.repetition,
+          cross_attentions: decoded.cross_attentions,
+        })
+        .collect(),
+    )
+  }
+
+  /// Probability of `<|nospeech|>` given the logits at the `<|startoftranscript|>` position.
+  fn no_speech_prob(
+    &self,
+    logits: ArrayView1<f32>,
+  ) -> Option<f32> {
+    let no_speech_token_id = usize::try_from(self.config.no_speech_token_id?).ok()?;
+    decoding::log_softmax(logits)
+      .get(no_speech_token_id)
+      .map(|logprob| logprob.exp())
+  }
+
+  /// Drops the repeats of a repetition loop at the end of `tokens`. Timestamps are ignored when
+  /// comparing, they keep increasing while the text loops.
+  fn truncate_repetition_loop(
+    &self,
+    hypothesis: &mut Hypothesis,
+    min_repeats: usize,
+  ) -> bool {
+    let text_positions: Vec<usize> = hypothesis
+      .tokens
+      .iter()
+      .enumerate()
+      .filter(|&(_, &token)| token < self.config.eos_token_id)
+      .map(|(position, _)| position)
+      .collect();
+    let text: Vec<i64> = text_positions
+      .iter()
+      .map(|&position| hypothesis.tokens[position])
+      .collect();
+
+    match decoding::repetition_loop(&text, min_repeats) {
+      Some(keep) => {
+        hypothesis.truncate(text_positions[keep]);
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Generates one token at a time for every row of the batch, picking the most likely one at
+  /// temperature zero and sampling otherwise. Rows that reached the end of text keep being fed to
+  /// the decoder until every row is done, their outputs are ignored.
+  #[allow(clippy::too_many_arguments)]
+  fn sample(
+    &mut self,
+    encoder_hidden_states: &DynValue,
+    decoder_input_ids: Array2<i64>,
+    sot_index: usize,
+    gen_config: &GenerationConfig,
+    temperature: f32,
+    alignment_heads: Option<&[(usize, usize)]>,
+    on_token: &mut dyn FnMut(usize, i64),
+  ) -> Result<Vec<DecodedSequence>> {
+    let batch_size = decoder_input_ids.nrows();
+    let mut rng = rand::rng();
+    let mut sequences: Vec<DecodedSequence> = (0..batch_size)
+      .map(|_| DecodedSequence {
+        hypothesis:       Hypothesis::default(),
+        cross_attentions: None,
+        no_speech_prob:   None,
+        repetition:       false,
+      })
+      .collect();
+    let mut cross_attentions: Vec<Vec<Array3<f32>>> = vec![Vec::new(); batch_size];
+    let mut finished = vec![false; batch_size];
+    let processors = self.logits_processors(gen_config);
+
+    // The first step feeds the whole prompt, every following step only feeds the token
+    // generated by the previous one and reuses the keys/values stored in the cache.
+    let mut cache = None;
+    let mut input_ids = decoder_input_ids;
+
+    for step in 0..gen_config.max_new_tokens {
+      self.check_cancelled()?;
+      let output = self.decoder.forward(
+        input_ids,
+        encoder_hidden_states,
+        cache.take(),
+        alignment_heads,
+      )?;
+      cache = Some(output.cache);
+
+      let mut next_tokens = vec![self.config.eos_token_id; batch_size];
+      for (row, sequence) in sequences.iter_mut().enumerate() {
+        if finished[row] {
+          continue;
+        }
+        if let Some(step_attentions) = &output.cross_attentions {
+          cross_attentions[row].push(
+            step_attentions
+              .index_axis(Axis(0), row)
+              .to_owned(),
+          );
+        }
+        if step == 0 {
+          sequence.no_speech_prob =
+            self.no_speech_prob(output.logits.slice(s![row, sot_index, ..]));
+        }
+
+        let mut next_token_logits = output.logits.slice(s![row, -1, ..]).to_owned();
+        processors.process(&sequence.hypothesis.tokens, next_token_logits.view_mut());
+        let next_token = decoding::sample_token(next_token_logits.view(), temperature, &mut rng);
+        let logprob = decoding::log_softmax(next_token_logits.view())[next_token];
+        sequence.hypothesis.sum_logprob += logprob;
+
+        let next_token = i64::try_from(next_token)?;
+        if next_token == self.config.eos_token_id {
+          finished[row] = true;
+          continue;
+        }
+
+        sequence.hypothesis.tokens.push(next_token);
+        sequence.hypothesis.token_logprobs.push(logprob);
+        on_token(row, next_token);
+        if let Some(min_repeats) = gen_config.repetition_threshold
+          && self.truncate_repetition_loop(&mut sequence.hypothesis, min_repeats)
+        {
+          sequence.repetition = true;
+          finished[row] = true;
+          continue;
+        }
+        next_tokens[row] = next_token;
+      }
+
+      if finished.iter().all(|&finished| finished) {
+        break;
+      }
+      input_ids = Array2::from_shape_vec((batch_size, 1), next_tokens)?;
+    }
+
+    for (sequence, cross_attentions) in sequences.iter_mut().zip(cross_attentions) {
+      if cross_attentions.is_empty() {
+        continue;
+      }
+      let views: Vec<_> = cross_attentions
+        .iter()
+        .map(|step| step.view())
+        .collect();
+      sequence.cross_attentions = Some(ndarray::concatenate(Axis(1), &views)?);
+    }
+
+    Ok(sequences)
+  }
+
+  /// Beam search, decoding every live beam as one row of the decoder batch.
+  fn beam_search(
+    &mut self,
+    encoder_hidden_states: &DynValue,
+    decoder_input_ids: &[i64],
+    sot_index: usize,
+    gen_config: &GenerationConfig,
+  ) -> Result<DecodedSequence> {
+    let mut search = BeamSearch::new(
+      gen_config.beam_size,
+      gen_config.patience,
+      self.config.eos_token_id,
+    );
+    let processors = self.logits_processors(gen_config);
+
+    // The first step runs the prompt once, the cache is then expanded to one row per beam.
+    let mut input_ids =
+      Array2::from_shape_vec((1, decoder_input_ids.len()), decoder_input_ids.to_vec())?;
+    let mut cache = None;
+    let mut beam_hidden_states = None;
+    let mut no_speech_prob = None;
+
+    for step in 0..gen_config.max_new_tokens {
+      self.check_cancelled()?;
+      let output = self.decoder.forward(
+        input_ids,
+        beam_hidden_states
+          .as_ref()
+          .unwrap_or(encoder_hidden_states),
+        cache.take(),
+        None,
+      )?;
+      if step == 0 {
+        no_speech_prob = self.no_speech_prob(output.logits.slice(s![0, sot_index, ..]));
+      }
+
+      let mut logprobs = output.logits.slice(s![.., -1, ..]).to_owned();
+      for (beam, mut row) in logprobs.outer_iter_mut().enumerate() {
+        processors.process(search.tokens(beam), row.view_mut());
+        let row_logprobs = decoding::log_softmax(row.view());
+        row.assign(&row_logprobs);
+      }
+
+      let sources = search.step(logprobs.view());
+      if search.is_finished() {
+        break;
+      }
+
+      let mut next_cache = output.cache;
+      next_cache.reorder(&sources)?;
+      cache = Some(next_cache);
+      if beam_hidden_states.is_none() {
+        beam_hidden_states = Some(repeat_batch(encoder_hidden_states, search.num_beams())?);
+      }
+      input_ids = Array2::from_shape_vec((search.num_beams(), 1), search.last_tokens())?;
+    }
+
+    let mut hypothesis = search.finish(gen_config.length_penalty);
+    let repetition = gen_config
+      .repetition_threshold
+      .is_some_and(|min_repeats| self.truncate_repetition_loop(&mut hypothesis, min_repeats));
+
+    Ok(DecodedSequence {
+      hypothesis,
+      cross_attentions: None,
+      no_speech_prob,
+      repetition,
+    })
+  }
+
+  /// Runs the decoder over the prompt and the generated tokens in one pass and returns the
+  /// cross attention of the alignment heads, shaped `[heads, positions, encoder_length]`.
+  fn alignment_cross_attentions(
+    &mut self,
+    encoder_hidden_states: &DynValue,
+    decoder_input_ids: &[i64],
+    tokens: &[i64],
+    alignment_heads: &[(usize, usize)],
+  ) -> Result<Array3<f32>> {
+    let input_ids: Vec<i64> = decoder_input_ids
+      .iter()
+      .chain(tokens)
+      .copied()
+      .collect();
+    let input_ids = Array2::from_shape_vec((1, input_ids.len()), input_ids)?;
+
+    let output = self.decoder.forward(
+      input_ids,
+      encoder_hidden_states,
+      None,
+      Some(alignment_heads),
+    )?;
+    output
+      .cross_attentions
+      .map(|cross_attentions| cross_attentions.index_axis_move(Axis(0), 0))
+      .ok_or_else(|| anyhow!("decoder did not return cross attentions"))
+  }
+}
+
+/// Number of windows in a batch of encoder outputs.
+fn hidden_states_batch_size(encoder_hidden_states: &DynValue) -> Result<usize> {
+  batch_size(encoder_hidden_states)
+    .filter(|&batch_size| batch_size > 0)
+    .ok_or_else(|| anyhow!("encoder output has no batch axis"))
+}
+
+/// Known Whisper checkpoints with an ONNX export. Any other export can be loaded by its Hugging
+/// Face id, see [`crate::models::whisper_model_source`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WhichModel {
+  Tiny,
+  #[value(name = "tiny.en")]
+  TinyEn,
+  Base,
+  #[value(name = "base.en")]
+  BaseEn,
+  Small,
+  #[value(name = "small.en")]
+  SmallEn,
+  Medium,
+  #[value(name = "medium.en")]
+  MediumEn,
+  #[value(alias = "largev3")]
+  LargeV3,
+  #[value(alias = "largev3turbo")]
+  LargeV3Turbo,
+  #[value(name = "distil-small.en")]
+  DistilSmallEn,
+  #[value(name = "distil-medium.en")]
+  DistilMediumEn,
+  DistilLargeV3,
+}
+
+impl WhichModel {
+  pub const fn model_and_revision(self) -> (&'static str, &'static str) {
+    match self {
+      Self::Tiny => ("onnx-community/whisper-tiny-ONNX", "main"),
+      Self::TinyEn => ("onnx-community/whisper-tiny.en", "main"),
+      Self::Base => ("onnx-community/whisper-base-ONNX", "main"),
+      Self::BaseEn => ("onnx-community/whisper-base.en", "main"),
+      Self::Small => ("onnx-community/whisper-small-ONNX", "main"),
+      Self::SmallEn => ("onnx-community/whisper-small.en", "main"),
+      Self::Medium => ("onnx-community/whisper-medium-ONNX", "main"),
+      Self::MediumEn => ("onnx-community/whisper-medium.en", "main"),
+      Self::LargeV3 => ("onnx-community/whisper-large-v3-ONNX", "main"),
+      Self::LargeV3Turbo => ("onnx-community/whisper-large-v3-turbo-ONNX", "main"),
+      Self::DistilSmallEn => ("distil-whisper/distil-small.en", "main"),
+      Self::DistilMediumEn => ("distil-whisper/distil-medium.en", "main"),
+      Self::DistilLargeV3 => ("distil-whisper/distil-large-v3", "main"),
+    }
+  }
+}
+
+/// A transcribed phrase and the time span it was spoken in, in seconds from the start of the audio.
+#[derive(Serialize, specta::Type, Clone, Debug)]
+pub struct TranscriptionSegment {
+  pub start:             f32,
+  pub end:               f32,
+  pub text:              String,
+  /// Generated token ids of the segment, including its timestamp tokens.
+  pub tokens:            Vec<i64>,
+  /// Log-probability of every token of `tokens`.
+  pub token_logprobs:    Vec<f32>,
+  /// Mean of `token_logprobs`, low values mean the model was unsure of the text.
+  pub avg_logprob:       f32,
+  /// Compression ratio of the segment text, high values point to repeated text.
+  pub compression_ratio: f32,
+  /// Probability that the window of the segment contains no speech, when the model can tell.
+  pub no_speech_prob:    Option<f32>,
+  /// Word-level timing, only present when word timestamps were requested.
+  pub words:             Option<Vec<TranscriptionWord>>,
+}
+
+#[derive(Serialize, specta::Type, Clone, Debug)]
+pub struct TranscriptionWord {
+  pub word:  String,
+  pub start: f32,
+  pub end:   f32,
+}
+
+#[derive(Serialize, specta::Type, Clone, Debug)]
+pub struct TranscriptionResult {
+  pub text:       String,
+  /// Language code the audio was transcribed in, either requested or detected.
+  pub language:   Option<String>,
+  pub segments:   Vec<TranscriptionSegment>,
+  /// At least one window was judged to be silence, its output was left out of the text.
+  pub no_speech:  bool,
+  /// At least one window ended in a repetition loop, the repeats were left out of the text.
+  pub repetition: bool,
+}
+
+/// A token sent to the [`TokenListener`] while a window is being decoded.
+#[derive(Serialize, specta::Type, Clone, Debug)]
+pub struct StreamedToken {
+  /// Index of the chunk in [`WhisperPipeline::transcribe_batch`], `0` otherwise.
+  pub chunk:       usize,
+  /// Index of the 30 second window within the chunk.
+  pub window:      usize,
+  pub token:       i64,
+  /// Text added by the token, empty for special and timestamp tokens, and for bytes of a
+  /// character that is not complete yet.
+  pub text:        String,
+  /// Temperature the window is decoded at. A window decoded again at a higher temperature starts
+  /// over, the text streamed for it before should be dropped.
+  pub temperature: f32,
+}
+
+/// Receives every token while it gets decoded. The streamed text is a preview, the final result
+/// may still drop parts of it (silent windows, repetition loops, unfinished segments).
+pub type TokenListener = Box<dyn FnMut(StreamedToken) + Send>;
+
+/// Most text tokens held back waiting for the rest of a character, the longest UTF-8 character has
+/// four bytes and byte-level tokens hold at least one each.
+const MAX_PENDING_TOKENS: usize = 4;
+
+/// Detokenizes the tokens of one window incrementally. Only the tokens since the last complete
+/// character are decoded again, byte-level tokens decode to the same text alone as in context.
+#[derive(Default)]
+struct IncrementalText {
+  /// Tokens whose bytes do not form complete characters yet.
+  pending: Vec<i64>,
+}
+
+impl IncrementalText {
+  /// Adds a token and returns the text it added.
+  fn push(
+    &mut self,
+    tokenizer: &Tokenizer,
+    eos_token_id: i64,
+    token: i64,
+  ) -> String {
+    // Special and timestamp tokens add no text.
+    if token >= eos_token_id {
+      return String::new();
+    }
+
+    self.pending.push(token);
+    let Ok(text) = decode_tokens(tokenizer, eos_token_id, &self.pending) else {
+      self.pending.clear();
+      return String::new();
+    };
+
+    // Byte-level tokens can end in the middle of a character, which decodes to a replacement
+    // character until the rest of it arrives. Bytes that never complete one are let through, so
+    // that the text goes on after them.
+    if text.ends_with('\u{FFFD}') && self.pending.len() < MAX_PENDING_TOKENS {
+      return String::new();
+    }
+    self.pending.clear();
+    text
+  }
+}
+
+/// The segments of every window of one transcription.
+struct WindowedTranscript {
+  segments:   Vec<TimestampedSegment>,
+  /// Language of the first window.
+  language:   Option<String>,
+  no_speech:  bool,
+  repetition: bool,
+}
+
+/// A pipeline that encapsulates the full Whisper transcription process.
+pub struct WhisperPipeline {
+  model:          Whisper,
+  processor:      WhisperProcessor,
+  tokenizer:      Tokenizer,
+  token_listener: Option<TokenListener>,
+}
+
+impl WhisperPipeline {
+  pub fn new(
+    source: ModelSource,
+    precision: ModelPrecision,
+    session_options: &SessionOptions,
+    cache: &OptimizedModelCache,
+    progress: Arc<dyn ProgressEmitter>,
+  ) -> Result<Self> {
+    let files = ModelFiles::new(source, progress)?;
+    files.require(REQUIRED_FILES)?;
+    files.require(&[precision.model_file("encoder_model").as_str()])?;
+
+    let model = Whisper::new(&files, precision, session_options, cache)?;
+
+    // The feature extractor follows the encoder, large-v3 and its derivatives use 128 mel bins.
+    let processor = WhisperProcessor::new(model.num_mel_bins()?)?;
+
+    let tokenizer_path = files.get("tokenizer.json")?;
+
+    let tokenizer = Tokenizer::from_file(tokenizer_path)
+      .map_err(|e| anyhow!("Failed to load tokenizer: {}", e))?;
+
+    Ok(Self {
+      model,
+      processor,
+      tokenizer,
+      token_listener: None,
+    })
+  }
+
+  /// Streams the tokens of the following transcriptions to `listener`, or stops streaming them.
+  pub fn set_token_listener(
+    &mut self,
+    listener: Option<TokenListener>,
+  ) {
+    self.token_listener = listener;
+  }
+
+  /// Cancels the following transcriptions once `cancellation` is cancelled, between two decoder
+  /// steps.
+  pub fn set_cancellation_token(
+    &mut self,
+    cancellation: Option<CancellationToken>,
+  ) {
+    self.model.set_cancellation_token(cancellation);
+  }
+
+  /// Transcribes audio of any length.
+  ///
+  /// Audio longer than a single 30 second window is transcribed window by window; when timestamps
+  /// are enabled, each window starts where the last complete segment of the previous one ended.
+  pub fn transcribe(
+    &mut self,
+    audio: &[f32],
+    gen_config: &GenerationConfig,
+  ) -> Result<String> {
+    Ok(
+      self
+        .transcribe_with_timestamps(audio, gen_config)?
+        .text,
+    )
+  }
+
+  /// Same as [`Self::transcribe`], but keeps the segments and their timestamps.
+  pub fn transcribe_with_timestamps(
+    &mut self,
+    audio: &[f32],
+    gen_config: &GenerationConfig,
+  ) -> Result<TranscriptionResult> {
+    let transcript = self.transcribe_segments(audio, gen_config)?;
+    self.transcription_result(transcript)
+  }
+
+  /// Transcribes independent chunks of audio, e.g. a backlog of utterances, encoding and decoding up
+  /// to `gen_config.batch_size` windows at a time.
+  ///
+  /// Unlike [`Self::transcribe_with_timestamps`], windows are not conditioned on the text before
+  /// them (`initial_prompt` is still used) and chunks longer than 30 seconds are cut into fixed
+  /// windows instead of seeking to the end of the last complete segment, so that every window can
+  /// be decoded at the same time.
+  pub fn transcribe_batch(
+    &mut self,
+    chunks: &[Vec<f32>],
+    gen_config: &GenerationConfig,
+  ) -> Result<Vec<TranscriptionResult>> {
+    let timestamp_begin = if gen_config.return_timestamps {
+      self.model.timestamp_begin()
+    } else {
+      None
+    };
+
+    let context = match &gen_config.initial_prompt {
+      Some(prompt) => self.encode_text(&format!(" {}", prompt.trim()))?,
+      None => Vec::new(),
+    };
+    let prompt = self.prompt_tokens(&context)?;
+
+    // Every window of every chunk, as the index of its chunk and its first sample.
+    let windows: Vec<(usize, usize)> = chunks
+      .iter()
+      .enumerate()
+      .flat_map(|(index, chunk)| {
+        (0..chunk.len().max(1))
+          .step_by(N_SAMPLES)
+          .map(move |seek| (index, seek))
+      })
+      .collect();
+
+    let mut transcripts: Vec<WindowedTranscript> = chunks
+      .iter()
+      .map(|_| WindowedTranscript {
+        segments:   Vec::new(),
+        language:   None,
+        no_speech:  false,
+        repetition: false,
+      })
+      .collect();
+
+    for batch in windows.chunks(gen_config.batch_size.max(1)) {
+      let features: Vec<_> = batch
+        .iter()
+        .map(|&(index, seek)| {
+          let chunk = &chunks[index];
+          self
+            .processor
+            .process(&chunk[seek..chunk.len().min(seek + N_SAMPLES)])
+        })
+        .collect();
+      let views: Vec<_> = features
+        .iter()
+        .map(|features| features.view())
+        .collect();
+      let input_features = ndarray::stack(Axis(0), &views)?;
+
+      let window_ids: Vec<(usize, usize)> = batch
+        .iter()
+        .map(|&(index, seek)| (index, seek / N_SAMPLES))
+        .collect();
+      let outputs = self.generate_batch_with_fallback(
+        input_features.view(),
+        &prompt,
+        gen_config,
+        &window_ids,
+      )?;
+      for (&(index, seek), output) in batch.iter().zip(outputs) {
+        let window_samples = chunks[index].len().min(seek + N_SAMPLES) - seek;
+        let transcript = &mut transcripts[index];
+        if transcript.language.is_none() {
+          transcript.language = output.language.clone();
+        }
+        if self.is_silent(&output, gen_config) {
+          transcript.no_speech = true;
+          continue;
+        }
+        transcript.repetition |= output.repetition;
+
+        let window_segments = self.window_segments(
+          &output,
+          timestamp_begin,
+          seek as f32 / SAMPLE_RATE as f32,
+          window_samples,
+          true,
+        )?;
+        transcript
+          .segments
+          .extend(window_segments.segments);
+      }
+    }
+
+    transcripts
+      .into_iter()
+      .map(|transcript| self.transcription_result(transcript))
+      .collect()
+  }
+
+  fn transcription_result(
+    &self,
+    transcript: WindowedTranscript,
+  ) -> Result<TranscriptionResult> {
+    let tokens: Vec<i64> = transcript
+      .segments
+      .iter()
+      .flat_map(|segment| segment.tokens.iter().copied())
+      .collect();
+    let text = self.decode_text(&tokens)?.trim().to_string();
+
+    let segments = transcript
+      .segments
+      .into_iter()
+      .map(|segment| {
+        let text = self
+          .decode_text(&segment.tokens)?
+          .trim()
+          .to_string();
+        let avg_logprob =
+          segment.token_logprobs.iter().sum::<f32>() / segment.token_logprobs.len().max(1) as f32;
+
+        Ok(TranscriptionSegment {
+          start: segment.start,
+          end: segment.end,
+          compression_ratio: decoding::compression_ratio(&text),
+          text,
+          tokens: segment.tokens,
+          token_logprobs: segment.token_logprobs,
+          avg_logprob,
+          no_speech_prob: segment.no_speech_prob,
+          words: segment.words,
+        })
+      })
+      .collect::<Result<_>>()?;
+
+    Ok(TranscriptionResult {
+      text,
+      language: transcript.language,
+      segments,
+      no_speech: transcript.no_speech,
+      repetition: transcript.repetition,
+    })
+  }
+
+  /// Detects the language spoken in the first 30 seconds of the audio.
+  pub fn detect_language(
+    &mut self,
+    audio: &[f32],
+  ) -> Result<Vec<LanguageProbability>> {
+    let input_features = self.processor.process(audio).insert_axis(Axis(0));
+    self
+      .model
+      .detect_language(input_features.view())?
+      .pop()
+      .ok_or_else(|| anyhow!("language detection did not return a result"))
+  }
+
+  fn transcribe_segments(
+    &mut self,
+    audio: &[f32],
+    gen_config: &GenerationConfig,
+  ) -> Result<WindowedTranscript> {
+    let timestamp_begin = if gen_config.return_timestamps {
+      self.model.timestamp_begin()
+    } else {
+      None
+    };
+
+    let eos_token_id = self.model.eos_token_id();
+    let mut context = match &gen_config.initial_prompt {
+      Some(prompt) => self.encode_text(&format!(" {}", prompt.trim()))?,
+      None => Vec::new(),
+    };
+    if let Some(previous_text) = &gen_config.previous_text {
+      context.extend(self.encode_text(&format!(" {}", previous_text.trim()))?);
+    }
+
+    let mut transcript = WindowedTranscript {
+      segments:   Vec::new(),
+      language:   None,
+      no_speech:  false,
+      repetition: false,
+    };
+    let mut seek = 0;
+    let mut window_index = 0;
+
+    loop {
+      let window = &audio[seek..audio.len().min(seek + N_SAMPLES)];
+
+      // Process the raw audio into a mel spectrogram with the correct shape [80, 3000] for normal, and [128, 3000] for large-v3
+      let input_features = self.processor.process(window);
+
+      // Add the batch dimension, making the shape [1, 80, 3000] for normal, and [1, 128, 3000] for large-v3
+      let input_features = input_features.insert_axis(Axis(0));
+
+      let prompt = self.prompt_tokens(&context)?;
+      let output =
+        self.generate_with_fallback(input_features.view(), &prompt, gen_config, window_index)?;
+      window_index += 1;
+      if transcript.language.is_none() {
+        transcript.language = output.language.clone();
+      }
+
+      if self.is_silent(&output, gen_config) {
+        transcript.no_speech = true;
+        seek += window.len();
+        if seek >= audio.len() {
+          break;
+        }
+        continue;
+      }
+      transcript.repetition |= output.repetition;
+
+      let window_segments = self.window_segments(
+        &output,
+        timestamp_begin,
+        seek as f32 / SAMPLE_RATE as f32,
+        window.len(),
+        false,
+      )?;
+
+      // Only the kept segments prompt the next window, an unfinished one is decoded again there.
+      if !gen_config.condition_on_previous_text || output.temperature > PROMPT_RESET_TEMPERATURE {
+        context.clear();
+      } else {
+        context.extend(
+          window_segments
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.tokens)
+            .filter(|&&token| token < eos_token_id),
+        );
+      }
+
+      transcript
+        .segments
+        .extend(window_segments.segments);
+      seek += window_segments.consumed_samples;
+
+      if seek >= audio.len() {
+        break;
+      }
+    }
+
+    Ok(transcript)
+  }
+
+  /// Whatever was decoded from a silent window is most likely made up, it is skipped whole.
+  fn is_silent(
+    &self,
+    output: &GenerationOutput,
+    gen_config: &GenerationConfig,
+  ) -> bool {
+    output.exceeds_no_speech_threshold(gen_config)
+      && gen_config
+        .logprob_threshold
+        .is_none_or(|threshold| output.avg_logprob < threshold)
+  }
+
+  /// Splits the output of one window into segments, with their token log-probabilities and, when
+  /// the cross attention was collected, their words.
+  ///
+  /// With `keep_unfinished`, an unfinished segment at the end of the window is kept instead of
+  /// being left for the next window, see [`split_window_keeping_unfinished`].
+  fn window_segments(
+    &self,
+    output: &GenerationOutput,
+    timestamp_begin: Option<i64>,
+    time_offset: f32,
+    window_samples: usize,
+    keep_unfinished: bool,
+  ) -> Result<WindowSegments> {
+    let mut window_segments = match timestamp_begin {
+      Some(timestamp_begin) if keep_unfinished => split_window_keeping_unfinished(
+        &output.tokens,
+        timestamp_begin,
+        time_offset,
+        window_samples,
+      ),
+      Some(timestamp_begin) => {
+        split_window(&output.tokens, timestamp_begin, time_offset, window_samples)
+      },
+      None => WindowSegments::whole(output.tokens.clone(), time_offset, window_samples),
+    };
+
+    // Segments are consecutive slices of the generated tokens.
+    let mut offset = 0;
+    for segment in &mut window_segments.segments {
+      let length = segment.tokens.len();
+      segment.token_logprobs = output
+        .token_logprobs
+        .get(offset..offset + length)
+        .map_or_else(Vec::new, <[f32]>::to_vec);
+      segment.no_speech_prob = output.no_speech_prob;
+      offset += length;
+    }
+
+    if let Some(cross_attentions) = &output.cross_attentions {
+      self.assign_words(
+        &mut window_segments,
+        output,
+        cross_attentions,
+        window_samples,
+        time_offset,
+      )?;
+    }
+
+    Ok(window_segments)
+  }
+
+  /// Builds the previous text prompt, `<|startofprev|>` followed by the most recent context tokens.
+  fn prompt_tokens(
+    &self,
+    context: &[i64],
+  ) -> Result<Vec<i64>> {
+    if context.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let start_of_previous = self
+      .tokenizer
+      .token_to_id("<|startofprev|>")
+      .ok_or_else(|| {
+        anyhow!("The tokenizer does not define <|startofprev|>, prompts are not supported")
+      })?;
+
+    let mut prompt = vec![i64::from(start_of_previous)];
+    prompt.extend_from_slice(&context[context.len().saturating_sub(MAX_PROMPT_TOKENS)..]);
+    Ok(prompt)
+  }
+
+  fn encode_text(
+    &self,
+    text: &str,
+  ) -> Result<Vec<i64>> {
+    let encoding = self
+      .tokenizer
+      .encode(text, false)
+      .map_err(|e| anyhow!("Failed to encode prompt: {}", e))?;
+    Ok(
+      encoding
+        .get_ids()
+        .iter()
+        .map(|&id| i64::from(id))
+        .collect(),
+    )
+  }
+
+  /// Decodes one window, moving on to the next temperature of `gen_config.temperatures` while the
+  /// output looks like a failure: too repetitive (a repetition loop or a high compression ratio)
+  /// or too unlikely (low average log-probability), unless the window looks silent. The output of
+  /// the last temperature is kept regardless.
+  fn generate_with_fallback(
+    &mut self,
+    input_features: ArrayView3<f32>,
+    context: &[i64],
+    gen_config: &GenerationConfig,
+    window_index: usize,
+  ) -> Result<GenerationOutput> {
+    self
+      .generate_batch_with_fallback(input_features, context, gen_config, &[(0, window_index)])?
+      .pop()
+      .ok_or_else(|| anyhow!("decoder did not return an output"))
+  }
+
+  /// Same as [`Self::generate_with_fallback`] for a batch of windows, encoded in a single run. Only
+  /// the windows that failed are decoded again at the next temperature.
+  ///
+  /// `window_ids` holds the chunk and window index of every window, as reported to the
+  /// [`TokenListener`].
+  fn generate_batch_with_fallback(
+    &mut self,
+    input_features: ArrayView3<f32>,
+    context: &[i64],
+    gen_config: &GenerationConfig,
+    window_ids: &[(usize, usize)],
+  ) -> Result<Vec<GenerationOutput>> {
+    let encoder_hidden_states = self.model.encode(input_features)?;
+    let languages = self
+      .model
+      .resolve_languages(&encoder_hidden_states, gen_config)?;
+
+    let temperatures = match gen_config.temperatures.as_slice() {
+      [] => &[0.0][..],
+      temperatures => temperatures,
+    };
+
+    let mut outputs: Vec<Option<GenerationOutput>> = languages.iter().map(|_| None).collect();
+    let mut pending: Vec<usize> = (0..languages.len()).collect();
+
+    for (attempt, &temperature) in temperatures.iter().enumerate() {
+      let is_last = attempt + 1 == temperatures.len();
+
+      let pending_hidden_states = if pending.len() == languages.len() {
+        None
+      } else {
+        Some(select_batch(&encoder_hidden_states, &pending)?)
+      };
+      let eos_token_id = self.model.eos_token_id();
+      let tokenizer = &self.tokenizer;
+      let token_listener = &mut self.token_listener;
+      let mut texts: Vec<IncrementalText> = pending
+        .iter()
+        .map(|_| IncrementalText::default())
+        .collect();
+      let mut on_token = |row: usize, token: i64| {
+        if let Some(token_listener) = token_listener.as_mut() {
+          let (chunk, window) = window_ids[pending[row]];
+          token_listener(StreamedToken {
+            chunk,
+            window,
+            token,
+            text: texts[row].push(tokenizer, eos_token_id, token),
+            temperature,
+          });
+        }
+      };
+
+      let pending_outputs = self.model.decode_batch(
+        pending_hidden_states
+          .as_ref()
+          .unwrap_or(&encoder_hidden_states),
+        context,
+        pending
+          .iter()
+          .map(|&index| languages[index].clone())
+          .collect(),
+        gen_config,
+        temperature,
+        &mut on_token,
+      )?;
+
+      let mut failed = Vec::new();
+      for (&index, output) in pending.iter().zip(pending_outputs) {
+        if is_last || !self.needs_fallback(&output, gen_config)? {
+          outputs[index] = Some(output);
+        } else {
+          failed.push(index);
+        }
+      }
+
+      pending = failed;
+      if pending.is_empty() {
+        break;
+      }
+    }
+
+    outputs
+      .into_iter()
+      .map(|output| output.ok_or_else(|| anyhow!("decoder did not return an output")))
+      .collect()
+  }
+
+  /// Whether the output should be decoded again at a higher temperature. Silence is not worth
+  /// retrying, the window gets skipped anyway.
+  fn needs_fallback(
+    &self,
+    output: &GenerationOutput,
+    gen_config: &GenerationConfig,
+  ) -> Result<bool> {
+    let compression_ratio = decoding::compression_ratio(&self.decode_text(&output.tokens)?);
+    let too_repetitive = output.repetition
+      || gen_config
+        .compression_ratio_threshold
+        .is_some_and(|threshold| compression_ratio > threshold);
+    let too_unlikely = gen_config
+      .logprob_threshold
+      .is_some_and(|threshold| output.avg_logprob < threshold);
+
+    if (!too_repetitive && !too_unlikely) || output.exceeds_no_speech_threshold(gen_config) {
+      return Ok(false);
+    }
+    info!(
+      "Decoding at temperature {} failed (compression ratio {:.2}, average log-probability {:.2}), retrying",
+      output.temperature, compression_ratio, output.avg_logprob
+    );
+    Ok(true)
+  }
+
+  /// Aligns the words of every segment of a window using the cross attention collected while decoding it.
+  fn assign_words(
+    &self,
+    window_segments: &mut WindowSegments,
+    output: &GenerationOutput,
+    cross_attentions: &Array3<f32>,
+    window_samples: usize,
+    time_offset: f32,
+  ) -> Result<()> {
+    // The previous text does not belong to this window's audio, it is left out of the alignment.
+    let cross_attentions = cross_attentions.slice(s![.., output.context_length.., ..]);
+    let eos_token_id = self.model.eos_token_id();
+
+    // Position `prompt_length - 1` is the one that predicts the first generated token. The
+    // position after the last token is missing when decoding hit the token limit.
+    let first = output.prompt_length - output.context_length - 1;
+    let positions: Vec<usize> =
+      alignment::text_token_positions(&output.tokens, first, eos_token_id)
+        .into_iter()
+        .filter(|&position| position < cross_attentions.dim().1)
+        .collect();
+    let text_times =
+      alignment::token_timestamps(cross_attentions, &positions, window_samples / HOP_LENGTH);
+    let token_times: Vec<f32> = alignment::generated_token_times(
+      &output.tokens,
+      &text_times,
+      eos_token_id,
+      window_samples as f32 / SAMPLE_RATE as f32,
+    )?
+    .into_iter()
+    .map(|time| time_offset + time)
+    .collect();
+
+    // Segments are consecutive slices of the generated tokens.
+    let mut offset = 0;
+    for segment in &mut window_segments.segments {
+      let length = segment.tokens.len();
+      segment.words = Some(alignment::words_from_tokens(
+        &self.tokenizer,
+        &segment.tokens,
+        &token_times[offset..=offset + length],
+        eos_token_id,
+        output.language.as_deref(),
+      )?);
+      offset += length;
+    }
+
+    Ok(())
+  }
+
+  /// Decodes the text tokens, dropping every special and timestamp token.
+  fn decode_text(
+    &self,
+    tokens: &[i64],
+  ) -> Result<String> {
+    decode_tokens(&self.tokenizer, self.model.eos_token_id(), tokens)
+  }
+}
+
+/// [`WhisperPipeline::decode_text`] for callers that hold other borrows of the pipeline.
+fn decode_tokens(
+  tokenizer: &Tokenizer,
+  eos_token_id: i64,
+  tokens: &[i64],
+) -> Result<String> {
+  // Convert tokens safely to u32 for tokenizer.decode
+  let text_tokens_u32: Vec<u32> = tokens
+    .iter()
+    .filter(|&&tok| tok < eos_token_id)
+    .map(|&tok| {
+      u32::try_from(tok).map_err(|e| {
+        anyhow!(
+          "token id out of range when converting to u32: {} ({})",
+          tok,
+          e
+        )
+      })
+    })
+    .collect::<Result<_, _>>()?;
+
+  tokenizer
+    .decode(&text_tokens_u32, true)
+    .map_err(|e| anyhow!("Failed to decode tokens: {}", e))
+}
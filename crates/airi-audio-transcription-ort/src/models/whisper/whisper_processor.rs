@@ -3,15 +3,12 @@ use byteorder::{ByteOrder, LittleEndian};
 use ndarray::{Array1, Array2, s};
 use rustfft::{FftPlanner, num_complex::Complex};
 
-use super::whisper::WhichModel;
-
 // Constants from the Whisper paper/implementation
-const SAMPLE_RATE: usize = 16000;
+pub const SAMPLE_RATE: usize = 16000;
 const N_FFT: usize = 400;
-const N_MELS: usize = 80; // <--- Crucial: Use 80 for base, 128 for large-v3
-const HOP_LENGTH: usize = 160;
+pub const HOP_LENGTH: usize = 160;
 const CHUNK_LENGTH: usize = 30;
-const N_SAMPLES: usize = CHUNK_LENGTH * SAMPLE_RATE; // 480000 samples
+pub const N_SAMPLES: usize = CHUNK_LENGTH * SAMPLE_RATE; // 480000 samples
 const N_FRAMES: usize = N_SAMPLES / HOP_LENGTH; // 3000 frames
 
 pub struct WhisperProcessor {
@@ -19,29 +16,16 @@ pub struct WhisperProcessor {
 }
 
 impl WhisperProcessor {
-  pub fn new(which_model: WhichModel) -> Result<Self> {
-    // Load the 80-bin mel filters
-    if which_model == WhichModel::LargeV3 || which_model == WhichModel::LargeV3Turbo {
-      let mel_bytes = include_bytes!("./melfilters128.bytes");
-      let mut mel_filters_vec = vec![0f32; mel_bytes.len() / 4];
-      LittleEndian::read_f32_into(mel_bytes, &mut mel_filters_vec);
-
-      // The shape is [80, 201]. N_FFT/2 + 1 = 201.
-      let mel_filters = Array2::from_shape_vec((N_MELS, N_FFT / 2 + 1), mel_filters_vec)
-        .map_err(|e| anyhow!("Failed to create mel filters array: {}", e))?;
-
-      Ok(Self { mel_filters })
-    } else {
-      let mel_bytes = include_bytes!("./melfilters.bytes");
-      let mut mel_filters_vec = vec![0f32; mel_bytes.len() / 4];
-      LittleEndian::read_f32_into(mel_bytes, &mut mel_filters_vec);
-
-      // The shape is [80, 201]. N_FFT/2 + 1 = 201.
-      let mel_filters = Array2::from_shape_vec((N_MELS, N_FFT / 2 + 1), mel_filters_vec)
-        .map_err(|e| anyhow!("Failed to create mel filters array: {}", e))?;
-
-      Ok(Self { mel_filters })
-    }
+  /// Creates the feature extractor of an encoder taking `num_mel_bins` mel bins. The filter banks
+  /// of the OpenAI checkpoints (80 and 128 bins) are bundled, other counts are computed.
+  pub fn new(num_mel_bins: usize) -> Result<Self> {
+    let mel_filters = match num_mel_bins {
+      80 => read_mel_filters(include_bytes!("./melfilters.bytes"), num_mel_bins)?,
+      128 => read_mel_filters(include_bytes!("./melfilters128.bytes"), num_mel_bins)?,
+      _ => mel_filter_bank(num_mel_bins),
+    };
+
+    Ok(Self { mel_filters })
   }
 
   /// Processes raw audio PCM data into a mel spectrogram.
@@ -132,3 +116,64 @@ impl WhisperProcessor {
     log_spec
   }
 }
+
+/// Reads a bundled filter bank, little-endian f32 values shaped `[num_mel_bins, N_FFT / 2 + 1]`.
+fn read_mel_filters(
+  mel_bytes: &[u8],
+  num_mel_bins: usize,
+) -> Result<Array2<f32>> {
+  let mut mel_filters_vec = vec![0f32; mel_bytes.len() / 4];
+  LittleEndian::read_f32_into(mel_bytes, &mut mel_filters_vec);
+
+  Array2::from_shape_vec((num_mel_bins, N_FFT / 2 + 1), mel_filters_vec)
+    .map_err(|e| anyhow!("Failed to create mel filters array: {}", e))
+}
+
+// Slaney mel scale: linear below 1 kHz, logarithmic above.
+const MEL_LINEAR_STEP: f64 = 200.0 / 3.0;
+const MEL_LOG_HZ: f64 = 1000.0;
+const MEL_LOG: f64 = MEL_LOG_HZ / MEL_LINEAR_STEP;
+
+fn mel_log_step() -> f64 {
+  6.4f64.ln() / 27.0
+}
+
+fn hz_to_mel(hz: f64) -> f64 {
+  if hz >= MEL_LOG_HZ {
+    MEL_LOG + (hz / MEL_LOG_HZ).ln() / mel_log_step()
+  } else {
+    hz / MEL_LINEAR_STEP
+  }
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+  if mel >= MEL_LOG {
+    MEL_LOG_HZ * (mel_log_step() * (mel - MEL_LOG)).exp()
+  } else {
+    mel * MEL_LINEAR_STEP
+  }
+}
+
+/// Computes a Slaney-normalized triangular filter bank from 0 Hz to the Nyquist frequency, the same
+/// as `librosa.filters.mel(sr=16000, n_fft=400, n_mels=num_mel_bins)` used by the reference
+/// implementation.
+fn mel_filter_bank(num_mel_bins: usize) -> Array2<f32> {
+  let num_frequencies = N_FFT / 2 + 1;
+  let min_mel = hz_to_mel(0.0);
+  let max_mel = hz_to_mel(SAMPLE_RATE as f64 / 2.0);
+  let mel_frequencies: Vec<f64> = (0..num_mel_bins + 2)
+    .map(|i| mel_to_hz(min_mel + (max_mel - min_mel) * i as f64 / (num_mel_bins + 1) as f64))
+    .collect();
+
+  Array2::from_shape_fn((num_mel_bins, num_frequencies), |(mel, bin)| {
+    let frequency = bin as f64 * SAMPLE_RATE as f64 / N_FFT as f64;
+    let (lower, center, upper) = (
+      mel_frequencies[mel],
+      mel_frequencies[mel + 1],
+      mel_frequencies[mel + 2],
+    );
+    let rising = (frequency - lower) / (center - lower);
+    let falling = (upper - frequency) / (upper - center);
+    (2.0 / (upper - lower) * rising.min(falling).max(0.0)) as f32
+  })
+}
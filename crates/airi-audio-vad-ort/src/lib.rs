@@ -0,0 +1,5 @@
+//! Silero VAD on ONNX Runtime, without Tauri. Download progress of the model is reported to a
+//! [`airi_ort_common::huggingface::ProgressEmitter`], the Tauri plugin forwards it to the window
+//! that loads it.
+
+pub mod models;
@@ -0,0 +1,23 @@
+pub mod silero_vad;
+use std::sync::Arc;
+
+use airi_ort_common::{
+  huggingface::ProgressEmitter,
+  model_source::ModelSource,
+  optimized_model_cache::OptimizedModelCache,
+  session_options::SessionOptions,
+};
+use log::info;
+
+pub fn new_silero_vad_processor(
+  progress: Arc<dyn ProgressEmitter>,
+  source: Option<ModelSource>,
+  session_options: &SessionOptions,
+  cache: &OptimizedModelCache,
+) -> anyhow::Result<silero_vad::Processor> {
+  let source =
+    source.unwrap_or_else(|| ModelSource::hugging_face("onnx-community/silero-vad", "main"));
+
+  info!("Loading VAD model from {}", source);
+  silero_vad::Processor::new(source, session_options, cache, progress)
+}
@@ -1,23 +1,18 @@
 use std::{path::PathBuf, sync::Arc};
 
+use airi_ort_common::{
+  huggingface::ProgressEmitter,
+  model_source::{ModelFiles, ModelSource},
+  optimized_model_cache::OptimizedModelCache,
+  session_options::SessionOptions,
+};
 use anyhow::Result;
-use hf_hub::Repo;
 use log::info;
-use ort::{
-  execution_providers::{
-    CPUExecutionProvider,
-    CUDAExecutionProvider,
-    CoreMLExecutionProvider,
-    DirectMLExecutionProvider,
-  },
-  session::{Session, builder::GraphOptimizationLevel},
-  util::Mutex,
-  value::Tensor,
-};
+use ort::{session::Session, util::Mutex, value::Tensor};
 use serde::{Deserialize, Serialize};
-use tauri::Runtime;
 
-use crate::helpers::huggingface::create_progress_emitter;
+/// Files of the Silero VAD export.
+pub const REQUIRED_FILES: &[&str] = &["onnx/model.onnx"];
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct VADInferenceResult {
@@ -37,38 +32,18 @@ pub struct Processor {
 }
 
 impl Processor {
-  pub fn new<R: Runtime>(window: tauri::WebviewWindow<R>) -> Result<Self> {
-    let model_id = "onnx-community/silero-vad";
-    let revision = "main";
-
-    let cache_api = hf_hub::Cache::from_env();
-    let cache_repo = cache_api.repo(Repo::with_revision(
-      model_id.into(),
-      hf_hub::RepoType::Model,
-      revision.into(),
-    ));
-
-    let api = hf_hub::api::sync::ApiBuilder::new().build()?;
-    let repo = api.repo(hf_hub::Repo::with_revision(
-      model_id.into(),
-      hf_hub::RepoType::Model,
-      revision.into(),
-    ));
-
-    let model_path_sub_name = "onnx/model.onnx";
-    let model_path = match cache_repo.get(model_path_sub_name) {
-      Some(path) => path,
-      None => repo.download_with_progress(
-        model_path_sub_name,
-        create_progress_emitter(
-          window.clone(),
-          "tauri-plugins:tauri-plugin-ipc-audio-vad-ort:load-model-silero-vad-progress",
-          model_path_sub_name.to_string(),
-        ),
-      )?,
-    };
+  pub fn new(
+    source: ModelSource,
+    session_options: &SessionOptions,
+    cache: &OptimizedModelCache,
+    progress: Arc<dyn ProgressEmitter>,
+  ) -> Result<Self> {
+    let files = ModelFiles::new(source, progress)?;
+    files.require(REQUIRED_FILES)?;
+
+    let model_path = files.get("onnx/model.onnx")?;
 
-    let session = Self::create_optimized_session(model_path.clone())?;
+    let session = Self::create_optimized_session(model_path.clone(), session_options, cache)?;
 
     Ok(Self {
       session: Arc::new(Mutex::new(session)),
@@ -76,23 +51,12 @@ impl Processor {
   }
 
   /// Create an optimized ONNX session with hardware acceleration
-  fn create_optimized_session(model_path: PathBuf) -> Result<Session> {
-    let builder = Session::builder()?
-      .with_optimization_level(GraphOptimizationLevel::Level3)?
-      .with_intra_threads(1)?;
-
-    let session = builder
-      .with_execution_providers(vec![
-        CUDAExecutionProvider::default()
-          .with_device_id(0)
-          .build(),
-        CoreMLExecutionProvider::default().build(),
-        DirectMLExecutionProvider::default()
-          .with_device_id(0)
-          .build(),
-        CPUExecutionProvider::default().build(),
-      ])?
-      .commit_from_file(model_path)?;
+  fn create_optimized_session(
+    model_path: PathBuf,
+    session_options: &SessionOptions,
+    cache: &OptimizedModelCache,
+  ) -> Result<Session> {
+    let session = cache.commit(&model_path, session_options)?;
     info!("VAD model loaded successfully");
 
     Ok(session)
@@ -1,10 +1,10 @@
-use log::error;
-use tauri::{Emitter, Runtime};
+use std::sync::Arc;
 
+/// Receives the download progress of model files, e.g. to forward it to the frontend or print it.
+/// Implementations decide where the progress goes, e.g. which event it is emitted as.
 pub trait ProgressEmitter: Send + Sync {
   fn emit_progress(
     &self,
-    event_name: String,
     filename: String,
     progress: f32,
     total_size: usize,
@@ -13,28 +13,24 @@ pub trait ProgressEmitter: Send + Sync {
 
   fn emit_done(
     &self,
-    event_name: String,
     filename: String,
   );
 }
 
 pub struct ModelLoadProgressEmitter {
-  event_name: String,
   filename:   String,
   size:       usize,
   total_size: usize,
   progress:   f32,
-  emitter:    Box<dyn ProgressEmitter>,
+  emitter:    Arc<dyn ProgressEmitter>,
 }
 
 impl ModelLoadProgressEmitter {
   pub fn new(
-    emitter: Box<dyn ProgressEmitter>,
-    event_name: &str,
+    emitter: Arc<dyn ProgressEmitter>,
     filename: String,
   ) -> Self {
     Self {
-      event_name: event_name.to_string(),
       filename,
       size: 0,
       total_size: 0,
@@ -44,40 +40,11 @@ impl ModelLoadProgressEmitter {
   }
 }
 
-impl<R: Runtime> ProgressEmitter for tauri::WebviewWindow<R> {
-  fn emit_progress(
-    &self,
-    event_name: String,
-    filename: String,
-    progress: f32,
-    total_size: usize,
-    current_size: usize,
-  ) {
-    if let Err(err) = self.emit(
-      event_name.as_str(),
-      (false, filename, progress, total_size, current_size),
-    ) {
-      error!("Failed to emit model-load-progress: {:?}", err);
-    }
-  }
-
-  fn emit_done(
-    &self,
-    event_name: String,
-    filename: String,
-  ) {
-    if let Err(err) = self.emit(event_name.as_str(), (true, filename, 100.0)) {
-      error!("Failed to emit model-load-done: {:?}", err);
-    }
-  }
-}
-
 pub fn create_progress_emitter(
-  window: impl ProgressEmitter + 'static,
-  event_name: &str,
+  emitter: Arc<dyn ProgressEmitter>,
   filename: String,
 ) -> ModelLoadProgressEmitter {
-  ModelLoadProgressEmitter::new(Box::new(window), event_name, filename)
+  ModelLoadProgressEmitter::new(emitter, filename)
 }
 
 // Remove the generic <R: Runtime> since ModelLoadProgressEmitter no longer has generics
@@ -90,7 +57,6 @@ impl hf_hub::api::Progress for ModelLoadProgressEmitter {
     self.total_size = size;
     self.progress = 0.0;
     self.emitter.emit_progress(
-      self.event_name.clone(),
       self.filename.clone(),
       self.progress,
       self.total_size,
@@ -109,7 +75,6 @@ impl hf_hub::api::Progress for ModelLoadProgressEmitter {
       100.0
     };
     self.emitter.emit_progress(
-      self.event_name.clone(),
       self.filename.clone(),
       self.progress,
       self.total_size,
@@ -120,14 +85,11 @@ impl hf_hub::api::Progress for ModelLoadProgressEmitter {
   fn finish(&mut self) {
     self.progress = 100.0;
     self.emitter.emit_progress(
-      self.event_name.clone(),
       self.filename.clone(),
       self.progress,
       self.total_size,
       self.size,
     );
-    self
-      .emitter
-      .emit_done(self.event_name.clone(), self.filename.clone());
+    self.emitter.emit_done(self.filename.clone());
   }
 }
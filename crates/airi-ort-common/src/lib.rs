@@ -0,0 +1,8 @@
+//! What the ONNX Runtime models of AIRI have in common, without Tauri: where their files come
+//! from, how their sessions are created and cached, and the worker that runs their requests.
+
+pub mod huggingface;
+pub mod model_source;
+pub mod optimized_model_cache;
+pub mod session_options;
+pub mod worker;
@@ -0,0 +1,171 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use anyhow::{Result, anyhow};
+use hf_hub::{
+  Cache,
+  CacheRepo,
+  Repo,
+  RepoType,
+  api::sync::{ApiBuilder, ApiRepo},
+};
+
+use crate::huggingface::{ProgressEmitter, create_progress_emitter};
+
+/// Where the files of a model come from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelSource {
+  /// A Hugging Face repository, downloaded on first use and kept in the local cache.
+  HuggingFace { model_id: String, revision: String },
+  /// A local directory laid out like the repository, either with the `onnx/` sub-directory or
+  /// with every file at the top level.
+  Directory(PathBuf),
+  /// Explicit local paths, keyed by their path in the repository (e.g. `onnx/model.onnx`) or
+  /// just by their file name.
+  Files(HashMap<String, PathBuf>),
+}
+
+impl ModelSource {
+  pub fn hugging_face(
+    model_id: &str,
+    revision: &str,
+  ) -> Self {
+    Self::HuggingFace {
+      model_id: model_id.to_string(),
+      revision: revision.to_string(),
+    }
+  }
+
+  /// Builds a local source from the options of a load command, the directory taking precedence.
+  /// Returns `None` when neither is given.
+  pub fn local(
+    model_dir: Option<String>,
+    model_files: Option<HashMap<String, String>>,
+  ) -> Option<Self> {
+    match (model_dir, model_files) {
+      (Some(model_dir), _) => Some(Self::Directory(PathBuf::from(model_dir))),
+      (None, Some(model_files)) => Some(Self::Files(
+        model_files
+          .into_iter()
+          .map(|(file, path)| (file, PathBuf::from(path)))
+          .collect(),
+      )),
+      (None, None) => None,
+    }
+  }
+}
+
+impl fmt::Display for ModelSource {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>,
+  ) -> fmt::Result {
+    match self {
+      Self::HuggingFace { model_id, revision } => {
+        write!(f, "Hugging Face repository {}@{}", model_id, revision)
+      },
+      Self::Directory(path) => write!(f, "model directory {}", path.display()),
+      Self::Files(_) => write!(f, "given model files"),
+    }
+  }
+}
+
+/// Resolves the files of a [`ModelSource`] to local paths, downloading them with progress events
+/// when the source is a Hugging Face repository.
+pub struct ModelFiles {
+  source:   ModelSource,
+  hub:      Option<(CacheRepo, ApiRepo)>,
+  progress: Arc<dyn ProgressEmitter>,
+}
+
+impl ModelFiles {
+  pub fn new(
+    source: ModelSource,
+    progress: Arc<dyn ProgressEmitter>,
+  ) -> Result<Self> {
+    let hub = match &source {
+      ModelSource::HuggingFace { model_id, revision } => {
+        let repo = Repo::with_revision(model_id.clone(), RepoType::Model, revision.clone());
+        let api = ApiBuilder::new().with_progress(false).build()?;
+        Some((Cache::from_env().repo(repo.clone()), api.repo(repo)))
+      },
+      _ => None,
+    };
+
+    Ok(Self {
+      source,
+      hub,
+      progress,
+    })
+  }
+
+  /// Returns the local path of `file`, given as its path in the repository layout.
+  pub fn get(
+    &self,
+    file: &str,
+  ) -> Result<PathBuf> {
+    match &self.hub {
+      Some((cache_repo, repo)) => match cache_repo.get(file) {
+        Some(path) => Ok(path),
+        None => repo
+          .download_with_progress(
+            file,
+            create_progress_emitter(self.progress.clone(), file.to_string()),
+          )
+          .map_err(|e| anyhow!("failed to download {}: {}", file, e)),
+      },
+      None => self
+        .find_local(file)
+        .ok_or_else(|| anyhow!("{} does not contain {}", self.source, file)),
+    }
+  }
+
+  /// Fails with the list of missing files when any of `files` is missing from a local source.
+  /// Files of a Hugging Face repository are only checked when they get downloaded.
+  pub fn require(
+    &self,
+    files: &[&str],
+  ) -> Result<()> {
+    if self.hub.is_some() {
+      return Ok(());
+    }
+
+    let missing: Vec<&str> = files
+      .iter()
+      .copied()
+      .filter(|file| self.find_local(file).is_none())
+      .collect();
+    if missing.is_empty() {
+      return Ok(());
+    }
+
+    Err(anyhow!(
+      "{} is missing {} (expected {})",
+      self.source,
+      missing.join(", "),
+      files.join(", ")
+    ))
+  }
+
+  fn find_local(
+    &self,
+    file: &str,
+  ) -> Option<PathBuf> {
+    let file_name = Path::new(file).file_name()?.to_str()?;
+    match &self.source {
+      ModelSource::Directory(directory) => [directory.join(file), directory.join(file_name)]
+        .into_iter()
+        .find(|path| path.is_file()),
+      ModelSource::Files(files) => files
+        .get(file)
+        .or_else(|| files.get(file_name))
+        .filter(|path| path.is_file())
+        .cloned(),
+      ModelSource::HuggingFace { .. } => None,
+    }
+  }
+}
@@ -0,0 +1,382 @@
+use std::{
+  cmp::Reverse,
+  fs::{self, File},
+  path::{Path, PathBuf},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use log::{info, warn};
+use ort::session::Session;
+use sha2::{Digest, Sha256};
+
+use crate::session_options::{OptimizationLevel, SessionOptions};
+
+/// Most variants kept per model file, the least recently used ones are removed past it.
+const MAX_VARIANTS: usize = 3;
+/// Models whose graphs were not used for this long are removed, e.g. models that moved or were
+/// deleted since.
+const MAX_UNUSED_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// A variant ONNX Runtime failed to save is tried again after this long.
+const UNSUPPORTED_MARKER_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Keeps the graphs ONNX Runtime optimized in a cache directory, so that later starts skip the
+/// optimization.
+///
+/// A cached graph is stored in a directory of the model path, named after the version of the model
+/// file and the hash of everything else the optimization depends on: the ONNX Runtime build, the
+/// providers it may use and the optimization level. Each combination of them is a variant of its
+/// own, switching between configurations keeps the graphs of the others. Writing a variant removes
+/// the graphs of the earlier versions of the model, the least recently used variants past
+/// [`MAX_VARIANTS`] and the models unused for [`MAX_UNUSED_AGE`].
+///
+/// A variant ONNX Runtime failed to save while the model itself loads is marked, so that it is not
+/// optimized twice on every later start. The mark expires, in case the failure was transient.
+pub struct OptimizedModelCache {
+  dir: Option<PathBuf>,
+}
+
+impl OptimizedModelCache {
+  /// A cache in `dir`, disabled without one.
+  pub fn new(dir: Option<PathBuf>) -> Self {
+    Self { dir }
+  }
+
+  /// Creates a session for the model at `model_path`, from its cached optimized graph when there
+  /// is one, otherwise optimizing it and caching the result.
+  pub fn commit(
+    &self,
+    model_path: &Path,
+    options: &SessionOptions,
+  ) -> Result<Session> {
+    let uncached = || -> Result<Session> {
+      Ok(
+        options
+          .session_builder()?
+          .commit_from_file(model_path)?,
+      )
+    };
+
+    let cached_path = match self.cached_path(model_path, options) {
+      Ok(Some(cached_path)) => cached_path,
+      Ok(None) => return uncached(),
+      Err(e) => {
+        warn!(
+          "Failed to look up the optimized {}: {}",
+          model_path.display(),
+          e
+        );
+        return uncached();
+      },
+    };
+
+    // Saving this variant failed recently, it would most likely fail again after optimizing it.
+    let unsupported_path = cached_path.with_extension("unsupported");
+    if let Ok(marked) = fs::metadata(&unsupported_path).and_then(|metadata| metadata.modified()) {
+      if marked
+        .elapsed()
+        .is_ok_and(|age| age < UNSUPPORTED_MARKER_AGE)
+      {
+        return uncached();
+      }
+      let _ = fs::remove_file(&unsupported_path);
+    }
+
+    if cached_path.exists() {
+      // The cached graph is optimized already, optimizing it again would only take time.
+      let cached_options = SessionOptions {
+        optimization_level: OptimizationLevel::Disable,
+        ..options.clone()
+      };
+      match cached_options
+        .session_builder()?
+        .commit_from_file(&cached_path)
+      {
+        Ok(session) => {
+          info!(
+            "Loaded optimized {} from {}",
+            model_path.display(),
+            cached_path.display()
+          );
+          touch(&cached_path);
+          return Ok(session);
+        },
+        Err(e) => {
+          warn!(
+            "Failed to load the cached {}, optimizing it again: {}",
+            cached_path.display(),
+            e
+          );
+          let _ = fs::remove_file(&cached_path);
+        },
+      }
+    }
+
+    if let Some(model_dir) = cached_path.parent() {
+      fs::create_dir_all(model_dir)?;
+    }
+
+    // Written next to its final path first, a partially written graph is never picked up.
+    let partial_path = cached_path.with_extension("partial");
+    let session = options
+      .session_builder()?
+      .with_optimized_model_path(&partial_path)?
+      .commit_from_file(model_path);
+    match session {
+      Ok(session) => {
+        match fs::rename(&partial_path, &cached_path) {
+          Ok(()) => self.prune(&cached_path),
+          Err(e) => warn!(
+            "Failed to cache the optimized {}: {}",
+            model_path.display(),
+            e
+          ),
+        }
+        Ok(session)
+      },
+      Err(e) => {
+        warn!(
+          "Failed to optimize {} into the cache, loading it without: {}",
+          model_path.display(),
+          e
+        );
+        let _ = fs::remove_file(&partial_path);
+        // Only a model that loads without the cache tells that saving it is what failed.
+        let session = uncached()?;
+        if let Err(e) = fs::write(&unsupported_path, e.to_string()) {
+          warn!("Failed to mark {}: {}", unsupported_path.display(), e);
+        }
+        Ok(session)
+      },
+    }
+  }
+
+  /// Removes what the variant just written at `cached_path` supersedes: the files of the other
+  /// versions of its model, its least recently used siblings past [`MAX_VARIANTS`], and the
+  /// models that were not used for [`MAX_UNUSED_AGE`].
+  fn prune(
+    &self,
+    cached_path: &Path,
+  ) {
+    let (Some(dir), Some(model_dir), Some(version)) = (
+      &self.dir,
+      cached_path.parent(),
+      cached_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split_once('-'))
+        .map(|(version, _)| version),
+    ) else {
+      return;
+    };
+
+    let mut variants = Vec::new();
+    for (path, modified) in entries(model_dir) {
+      let current_version = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(&format!("{}-", version)));
+      if !current_version {
+        remove(&path);
+      } else if path
+        .extension()
+        .is_some_and(|extension| extension == "onnx")
+      {
+        variants.push((path, modified));
+      }
+    }
+    variants.sort_by_key(|(_, modified)| Reverse(*modified));
+    for (path, _) in variants.iter().skip(MAX_VARIANTS) {
+      remove(path);
+    }
+
+    for (path, _) in entries(dir) {
+      if path == model_dir || !path.is_dir() {
+        continue;
+      }
+      let last_used = entries(&path).map(|(_, modified)| modified).max();
+      if last_used.is_none_or(|last_used| {
+        last_used
+          .elapsed()
+          .is_ok_and(|age| age > MAX_UNUSED_AGE)
+      }) {
+        remove(&path);
+      }
+    }
+  }
+
+  /// Where the optimized graph of `model_path` is cached with `options`, `None` when it is not
+  /// cached at all.
+  fn cached_path(
+    &self,
+    model_path: &Path,
+    options: &SessionOptions,
+  ) -> Result<Option<PathBuf>> {
+    let Some(dir) = &self.dir else {
+      return Ok(None);
+    };
+    if options.optimization_level == OptimizationLevel::Disable {
+      return Ok(None);
+    }
+    // The optimized graph would point at weights stored next to the original model.
+    if has_external_data(model_path) {
+      return Ok(None);
+    }
+
+    // Only the providers that are available can leave their nodes in the graph.
+    let providers: Vec<_> = options
+      .providers
+      .iter()
+      .filter(|provider| provider.is_available())
+      .collect();
+    // ONNX Runtime cannot save the nodes these providers compiled into their own kernels.
+    if providers
+      .iter()
+      .any(|provider| provider.compiles_nodes())
+    {
+      return Ok(None);
+    }
+
+    let mut variant_hash = Sha256::new();
+    variant_hash.update(ort::info());
+    variant_hash.update(format!("{:?}", providers));
+    variant_hash.update(format!("{:?}", options.optimization_level));
+
+    let (path_key, version) = model_key(model_path)?;
+    Ok(Some(dir.join(path_key).join(format!(
+      "{}-{:x}.onnx",
+      version,
+      variant_hash.finalize()
+    ))))
+  }
+}
+
+/// Identifies the model file without reading it, models are hundreds of megabytes and more: a key
+/// of its path, and a version told apart from the earlier ones by its size and modification time.
+/// The files of the Hugging Face cache resolve to blobs named after their hash.
+fn model_key(model_path: &Path) -> Result<(String, String)> {
+  let path = fs::canonicalize(model_path)?;
+  let metadata = fs::metadata(&path)?;
+  let modified = metadata
+    .modified()?
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default();
+
+  let path_key = Sha256::digest(path.as_os_str().as_encoded_bytes());
+  let mut version = Sha256::new();
+  version.update(metadata.len().to_le_bytes());
+  version.update(modified.as_nanos().to_le_bytes());
+  Ok((
+    format!("{:x}", path_key),
+    format!("{:x}", version.finalize())[..16].to_string(),
+  ))
+}
+
+/// The entries of `dir` with their modification time, nothing when it cannot be read.
+fn entries(dir: &Path) -> impl Iterator<Item = (PathBuf, SystemTime)> {
+  fs::read_dir(dir)
+    .into_iter()
+    .flatten()
+    .flatten()
+    .filter_map(|entry| {
+      let modified = entry
+        .metadata()
+        .and_then(|metadata| metadata.modified());
+      Some((entry.path(), modified.ok()?))
+    })
+}
+
+fn remove(path: &Path) {
+  let removed = if path.is_dir() {
+    fs::remove_dir_all(path)
+  } else {
+    fs::remove_file(path)
+  };
+  match removed {
+    Ok(()) => info!("Removed the stale optimized model {}", path.display()),
+    Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+  }
+}
+
+/// Marks the cached graph as just used, the least recently used ones are removed first.
+fn touch(path: &Path) {
+  if let Err(e) = File::options()
+    .write(true)
+    .open(path)
+    .and_then(|file| file.set_modified(SystemTime::now()))
+  {
+    warn!("Failed to mark {} as used: {}", path.display(), e);
+  }
+}
+
+/// Whether the weights of the model are stored in a separate file, as exports over 2GB do.
+fn has_external_data(model_path: &Path) -> bool {
+  let mut data_path = model_path.as_os_str().to_owned();
+  data_path.push("_data");
+  Path::new(&data_path).exists()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn write_used(
+    path: &Path,
+    days_ago: u64,
+  ) {
+    fs::write(path, b"").unwrap();
+    File::options()
+      .write(true)
+      .open(path)
+      .unwrap()
+      .set_modified(SystemTime::now() - Duration::from_secs(days_ago * 24 * 60 * 60))
+      .unwrap();
+  }
+
+  #[test]
+  fn prune_removes_superseded_and_unused_graphs() {
+    let dir = std::env::temp_dir().join(format!("optimized-model-cache-{}", std::process::id()));
+    let model_dir = dir.join("model");
+    let moved_dir = dir.join("moved");
+    let recent_dir = dir.join("recent");
+    for dir in [&model_dir, &moved_dir, &recent_dir] {
+      fs::create_dir_all(dir).unwrap();
+    }
+
+    let written = model_dir.join("new-a.onnx");
+    write_used(&written, 0);
+    write_used(&model_dir.join("new-b.onnx"), 1);
+    write_used(&model_dir.join("new-c.onnx"), 2);
+    write_used(&model_dir.join("new-d.onnx"), 3);
+    write_used(&model_dir.join("new-e.unsupported"), 3);
+    write_used(&model_dir.join("old-a.onnx"), 0);
+    write_used(&moved_dir.join("old-a.onnx"), 40);
+    write_used(&recent_dir.join("old-a.onnx"), 10);
+
+    OptimizedModelCache::new(Some(dir.clone())).prune(&written);
+
+    let mut kept: Vec<_> = entries(&model_dir)
+      .map(|(path, _)| {
+        path
+          .file_name()
+          .unwrap()
+          .to_string_lossy()
+          .into_owned()
+      })
+      .collect();
+    kept.sort();
+    assert_eq!(
+      kept,
+      [
+        "new-a.onnx",
+        "new-b.onnx",
+        "new-c.onnx",
+        "new-e.unsupported"
+      ]
+    );
+    assert!(!moved_dir.exists());
+    assert!(recent_dir.join("old-a.onnx").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,194 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use ort::{
+  execution_providers::{
+    ArenaExtendStrategy,
+    CPUExecutionProvider,
+    CUDAExecutionProvider,
+    CoreMLExecutionProvider,
+    DirectMLExecutionProvider,
+    ExecutionProvider,
+    ExecutionProviderDispatch,
+  },
+  session::{
+    Session,
+    builder::{GraphOptimizationLevel, SessionBuilder},
+  },
+};
+use serde::{Deserialize, Serialize};
+
+/// An execution provider ONNX Runtime can run the models on.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum, specta::Type)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lowercase")]
+pub enum Provider {
+  Cuda,
+  CoreMl,
+  DirectMl,
+  Cpu,
+}
+
+impl Provider {
+  pub const ALL: [Provider; 4] = [
+    Provider::Cuda,
+    Provider::CoreMl,
+    Provider::DirectMl,
+    Provider::Cpu,
+  ];
+
+  /// Whether ONNX Runtime was built with this provider and it is supported on this platform. It
+  /// may still fail to load for a given model, e.g. when the drivers are missing.
+  pub fn is_available(&self) -> bool {
+    fn check(provider: impl ExecutionProvider) -> bool {
+      provider.supported_by_platform() && provider.is_available().unwrap_or(false)
+    }
+
+    match self {
+      Provider::Cuda => check(CUDAExecutionProvider::default()),
+      Provider::CoreMl => check(CoreMLExecutionProvider::default()),
+      Provider::DirectMl => check(DirectMLExecutionProvider::default()),
+      Provider::Cpu => check(CPUExecutionProvider::default()),
+    }
+  }
+
+  /// Whether the provider compiles the nodes it takes into kernels of its own, which ONNX Runtime
+  /// cannot save into an optimized graph.
+  pub fn compiles_nodes(&self) -> bool {
+    matches!(self, Provider::CoreMl | Provider::DirectMl)
+  }
+
+  fn dispatch(
+    &self,
+    options: &SessionOptions,
+  ) -> ExecutionProviderDispatch {
+    match self {
+      Provider::Cuda => CUDAExecutionProvider::default()
+        .with_device_id(0)
+        .with_arena_extend_strategy(options.arena_extend_strategy.into())
+        .build(),
+      Provider::CoreMl => CoreMLExecutionProvider::default().build(),
+      Provider::DirectMl => DirectMLExecutionProvider::default()
+        .with_device_id(0)
+        .build(),
+      Provider::Cpu => CPUExecutionProvider::default()
+        .with_arena_allocator(options.memory_arena)
+        .build(),
+    }
+  }
+}
+
+/// The providers available on this machine, see [`Provider::is_available`].
+pub fn available_providers() -> Vec<Provider> {
+  Provider::ALL
+    .into_iter()
+    .filter(Provider::is_available)
+    .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, specta::Type)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizationLevel {
+  Disable,
+  Level1,
+  Level2,
+  #[default]
+  Level3,
+}
+
+impl From<OptimizationLevel> for GraphOptimizationLevel {
+  fn from(level: OptimizationLevel) -> Self {
+    match level {
+      OptimizationLevel::Disable => GraphOptimizationLevel::Disable,
+      OptimizationLevel::Level1 => GraphOptimizationLevel::Level1,
+      OptimizationLevel::Level2 => GraphOptimizationLevel::Level2,
+      OptimizationLevel::Level3 => GraphOptimizationLevel::Level3,
+    }
+  }
+}
+
+/// How the memory arena of the GPU providers grows once it is full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ArenaGrowth {
+  /// Grows by larger and larger amounts, fewer allocations but more memory held.
+  #[default]
+  NextPowerOfTwo,
+  /// Grows by what was requested.
+  SameAsRequested,
+}
+
+impl From<ArenaGrowth> for ArenaExtendStrategy {
+  fn from(growth: ArenaGrowth) -> Self {
+    match growth {
+      ArenaGrowth::NextPowerOfTwo => ArenaExtendStrategy::NextPowerOfTwo,
+      ArenaGrowth::SameAsRequested => ArenaExtendStrategy::SameAsRequested,
+    }
+  }
+}
+
+/// How ONNX Runtime sessions are created. Applies to the models loaded afterwards.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, specta::Type)]
+#[serde(default)]
+pub struct SessionOptions {
+  /// Providers to run the models on, in order of preference. Whatever they cannot run falls back
+  /// to the CPU, even when it is not listed.
+  #[specta(optional)]
+  pub providers:             Vec<Provider>,
+  /// Threads running a single operator, ONNX Runtime picks one per core when unset. Cap it on
+  /// shared machines so that inference does not starve the other processes.
+  #[specta(optional)]
+  pub intra_threads:         Option<usize>,
+  /// Threads running independent operators at once, only used with `parallel_execution`.
+  #[specta(optional)]
+  pub inter_threads:         Option<usize>,
+  #[specta(optional)]
+  pub parallel_execution:    bool,
+  #[specta(optional)]
+  pub optimization_level:    OptimizationLevel,
+  /// Keeps the memory of the CPU provider for later runs instead of freeing it.
+  #[specta(optional)]
+  pub memory_arena:          bool,
+  /// Plans the memory of later runs from the first one, helps when the input shapes are fixed.
+  #[specta(optional)]
+  pub memory_pattern:        bool,
+  #[specta(optional)]
+  pub arena_extend_strategy: ArenaGrowth,
+}
+
+impl Default for SessionOptions {
+  fn default() -> Self {
+    Self {
+      providers:             Provider::ALL.to_vec(),
+      intra_threads:         None,
+      inter_threads:         None,
+      parallel_execution:    false,
+      optimization_level:    OptimizationLevel::default(),
+      memory_arena:          true,
+      memory_pattern:        true,
+      arena_extend_strategy: ArenaGrowth::default(),
+    }
+  }
+}
+
+impl SessionOptions {
+  /// A session builder with these options applied, ready to load a model.
+  pub fn session_builder(&self) -> Result<SessionBuilder> {
+    let mut builder = Session::builder()?
+      .with_optimization_level(self.optimization_level.into())?
+      .with_parallel_execution(self.parallel_execution)?
+      .with_memory_pattern(self.memory_pattern)?;
+    if let Some(threads) = self.intra_threads {
+      builder = builder.with_intra_threads(threads)?;
+    }
+    if let Some(threads) = self.inter_threads {
+      builder = builder.with_inter_threads(threads)?;
+    }
+
+    let providers: Vec<_> = self
+      .providers
+      .iter()
+      .map(|provider| provider.dispatch(self))
+      .collect();
+    Ok(builder.with_execution_providers(providers)?)
+  }
+}
@@ -0,0 +1,524 @@
+use std::{
+  cmp::Reverse,
+  panic::{AssertUnwindSafe, catch_unwind},
+  sync::{
+    Arc,
+    Condvar,
+    Mutex,
+    atomic::{AtomicBool, Ordering},
+  },
+  thread,
+};
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+/// How urgently a request should run. Queued requests run by priority, then in arrival order.
+#[derive(
+  Deserialize, Serialize, specta::Type, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+  /// Backlog work, e.g. re-transcribing recordings.
+  Background,
+  #[default]
+  Normal,
+  /// Live speech, someone is waiting for the result.
+  Live,
+}
+
+/// Per-request options of the inference commands.
+#[derive(Deserialize, specta::Type, Debug, Default)]
+pub struct RequestOptions {
+  /// Id chosen by the caller to cancel the request with.
+  #[specta(optional)]
+  pub id:       Option<String>,
+  #[specta(optional)]
+  pub priority: Option<Priority>,
+}
+
+/// Set once the request it belongs to is cancelled. Long running jobs check it between steps.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+
+  /// Fails when the request was cancelled.
+  pub fn check(&self) -> Result<()> {
+    if self.is_cancelled() {
+      return Err(anyhow!("The request was cancelled"));
+    }
+    Ok(())
+  }
+}
+
+#[derive(Serialize, specta::Type, Clone, Debug)]
+pub struct WorkerStatus {
+  /// Requests waiting for the worker.
+  pub queued:   usize,
+  /// Whether a request is running right now.
+  pub running:  bool,
+  /// Most requests that can wait, further requests are rejected.
+  pub capacity: usize,
+}
+
+/// A queued request with its response channel, whatever the type of its result.
+trait Job<M>: Send {
+  fn run(
+    self: Box<Self>,
+    model: &mut M,
+    cancellation: &CancellationToken,
+  );
+
+  /// Answers the request with `error` without running it.
+  fn reject(
+    self: Box<Self>,
+    error: anyhow::Error,
+  );
+}
+
+struct TypedJob<F, T> {
+  job:      F,
+  response: oneshot::Sender<Result<T>>,
+}
+
+impl<M, F, T> Job<M> for TypedJob<F, T>
+where
+  F: FnOnce(&mut M, &CancellationToken) -> Result<T> + Send,
+  T: Send,
+{
+  fn run(
+    self: Box<Self>,
+    model: &mut M,
+    cancellation: &CancellationToken,
+  ) {
+    let TypedJob { job, response } = *self;
+    let result = cancellation
+      .check()
+      .and_then(|_| job(model, cancellation));
+    // The caller may have stopped waiting, there is nobody left to tell.
+    let _ = response.send(result);
+  }
+
+  fn reject(
+    self: Box<Self>,
+    error: anyhow::Error,
+  ) {
+    let _ = self.response.send(Err(error));
+  }
+}
+
+struct QueuedJob<M> {
+  id:           Option<String>,
+  priority:     Priority,
+  sequence:     u64,
+  cancellation: CancellationToken,
+  job:          Box<dyn Job<M>>,
+}
+
+impl<M> QueuedJob<M> {
+  /// Jobs with a higher priority run first, then the ones that arrived first.
+  fn rank(&self) -> (Priority, Reverse<u64>) {
+    (self.priority, Reverse(self.sequence))
+  }
+}
+
+struct Queue<M> {
+  jobs:          Vec<QueuedJob<M>>,
+  next_sequence: u64,
+  /// Id and cancellation token of the running job.
+  running:       Option<(Option<String>, CancellationToken)>,
+  closed:        bool,
+}
+
+struct Shared<M> {
+  queue:     Mutex<Queue<M>>,
+  available: Condvar,
+  capacity:  usize,
+}
+
+/// A request submitted to an [`InferenceWorker`], resolved once it ran.
+pub struct PendingRequest<T>(oneshot::Receiver<Result<T>>);
+
+impl<T> PendingRequest<T> {
+  pub async fn wait(self) -> Result<T, String> {
+    match self.0.await {
+      Ok(result) => result.map_err(|e| e.to_string()),
+      Err(_) => Err("The request was dropped before it ran".to_string()),
+    }
+  }
+}
+
+/// Runs inference on a model from a dedicated thread, so that commands do not block the async
+/// runtime, and serves requests from a bounded priority queue.
+///
+/// Dropping the worker cancels the running request and drops the queued ones, the thread exits
+/// once the running request returns.
+pub struct InferenceWorker<M> {
+  shared: Arc<Shared<M>>,
+}
+
+impl<M: Send + 'static> InferenceWorker<M> {
+  pub fn spawn(
+    name: &str,
+    mut model: M,
+    capacity: usize,
+  ) -> Result<Self> {
+    let shared = Arc::new(Shared {
+      queue: Mutex::new(Queue {
+        jobs:          Vec::new(),
+        next_sequence: 0,
+        running:       None,
+        closed:        false,
+      }),
+      available: Condvar::new(),
+      capacity,
+    });
+
+    let worker_shared = shared.clone();
+    thread::Builder::new()
+      .name(name.to_string())
+      .spawn(move || {
+        while let Some(job) = worker_shared.next_job() {
+          // A panicking job drops its response, the worker keeps serving the other requests.
+          let QueuedJob {
+            job, cancellation, ..
+          } = job;
+          if catch_unwind(AssertUnwindSafe(|| job.run(&mut model, &cancellation))).is_err() {
+            warn!("An inference request panicked");
+          }
+          worker_shared.queue.lock().unwrap().running = None;
+        }
+        info!("Inference worker stopped");
+      })?;
+
+    Ok(Self { shared })
+  }
+
+  /// Queues `job`. When the queue is full, the queued request of the lowest priority makes room
+  /// for it if `job` has a higher one, otherwise `job` is rejected.
+  pub fn submit<T: Send + 'static>(
+    &self,
+    request: RequestOptions,
+    job: impl FnOnce(&mut M, &CancellationToken) -> Result<T> + Send + 'static,
+  ) -> Result<PendingRequest<T>, String> {
+    let priority = request.priority.unwrap_or_default();
+    let (sender, receiver) = oneshot::channel();
+
+    let mut queue = self.shared.queue.lock().unwrap();
+    if queue.jobs.len() >= self.shared.capacity {
+      let lowest = queue
+        .jobs
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, job)| job.rank())
+        .filter(|(_, job)| job.priority < priority)
+        .map(|(index, _)| index);
+      match lowest {
+        Some(index) => {
+          let dropped = queue.jobs.swap_remove(index);
+          info!(
+            "Inference queue is full, dropped a {:?} request",
+            dropped.priority
+          );
+          dropped.job.reject(anyhow!(
+            "The request was dropped from the full queue for a request of a higher priority"
+          ));
+        },
+        None => {
+          return Err(format!(
+            "The inference queue is full ({} requests)",
+            self.shared.capacity
+          ));
+        },
+      }
+    }
+
+    let sequence = queue.next_sequence;
+    queue.next_sequence += 1;
+    queue.jobs.push(QueuedJob {
+      id: request.id,
+      priority,
+      sequence,
+      cancellation: CancellationToken::default(),
+      job: Box::new(TypedJob {
+        job,
+        response: sender,
+      }),
+    });
+    drop(queue);
+    self.shared.available.notify_one();
+
+    Ok(PendingRequest(receiver))
+  }
+
+  /// Cancels every queued or running request with the given id. Returns whether any was found.
+  pub fn cancel(
+    &self,
+    id: &str,
+  ) -> bool {
+    let mut queue = self.shared.queue.lock().unwrap();
+
+    let (cancelled_jobs, jobs): (Vec<_>, Vec<_>) = std::mem::take(&mut queue.jobs)
+      .into_iter()
+      .partition(|job| job.id.as_deref() == Some(id));
+    queue.jobs = jobs;
+    let mut cancelled = !cancelled_jobs.is_empty();
+    for cancelled_job in cancelled_jobs {
+      cancelled_job
+        .job
+        .reject(anyhow!("The request was cancelled"));
+    }
+
+    if let Some((running_id, cancellation)) = &queue.running
+      && running_id.as_deref() == Some(id)
+    {
+      cancellation.cancel();
+      cancelled = true;
+    }
+    cancelled
+  }
+
+  pub fn status(&self) -> WorkerStatus {
+    let queue = self.shared.queue.lock().unwrap();
+    WorkerStatus {
+      queued:   queue.jobs.len(),
+      running:  queue.running.is_some(),
+      capacity: self.shared.capacity,
+    }
+  }
+}
+
+impl<M> Shared<M> {
+  /// Waits for the next job to run, `None` once the worker is closed.
+  fn next_job(&self) -> Option<QueuedJob<M>> {
+    let mut queue = self.queue.lock().unwrap();
+    loop {
+      if queue.closed {
+        return None;
+      }
+
+      let next = queue
+        .jobs
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, job)| job.rank())
+        .map(|(index, _)| index);
+      if let Some(index) = next {
+        let job = queue.jobs.swap_remove(index);
+        queue.running = Some((job.id.clone(), job.cancellation.clone()));
+        return Some(job);
+      }
+
+      queue = self.available.wait(queue).unwrap();
+    }
+  }
+}
+
+impl<M> Drop for InferenceWorker<M> {
+  fn drop(&mut self) {
+    let mut queue = self.shared.queue.lock().unwrap();
+    queue.closed = true;
+    for queued_job in queue.jobs.drain(..) {
+      queued_job
+        .job
+        .reject(anyhow!("The model was unloaded before the request ran"));
+    }
+    if let Some((_, cancellation)) = &queue.running {
+      cancellation.cancel();
+    }
+    drop(queue);
+    self.shared.available.notify_all();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{sync::mpsc, time::Duration};
+
+  use super::*;
+
+  /// Records the requests in the order they ran. Its sender disconnects once the worker thread
+  /// dropped it.
+  struct Model {
+    log:    Vec<&'static str>,
+    _alive: mpsc::Sender<()>,
+  }
+
+  fn spawn(capacity: usize) -> (InferenceWorker<Model>, mpsc::Receiver<()>) {
+    let (alive, stopped) = mpsc::channel();
+    let model = Model {
+      log:    Vec::new(),
+      _alive: alive,
+    };
+    (
+      InferenceWorker::spawn("test-inference", model, capacity).unwrap(),
+      stopped,
+    )
+  }
+
+  fn request(
+    id: &str,
+    priority: Priority,
+  ) -> RequestOptions {
+    RequestOptions {
+      id:       Some(id.to_string()),
+      priority: Some(priority),
+    }
+  }
+
+  /// Keeps the worker busy until the returned sender is used or dropped, so that the next
+  /// requests queue up. The request fails if it was cancelled in the meantime.
+  fn occupy(worker: &InferenceWorker<Model>) -> (mpsc::Sender<()>, PendingRequest<()>) {
+    let (started, has_started) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    let pending = worker
+      .submit(request("occupy", Priority::Live), move |_, cancellation| {
+        started.send(()).unwrap();
+        let _ = released.recv();
+        cancellation.check()
+      })
+      .unwrap();
+    has_started.recv().unwrap();
+    (release, pending)
+  }
+
+  fn record(
+    worker: &InferenceWorker<Model>,
+    label: &'static str,
+    priority: Priority,
+  ) -> Result<PendingRequest<Vec<&'static str>>, String> {
+    worker.submit(request(label, priority), move |model, _| {
+      model.log.push(label);
+      Ok(model.log.clone())
+    })
+  }
+
+  fn outcome<T>(pending: PendingRequest<T>) -> Result<T, String> {
+    match pending.0.blocking_recv() {
+      Ok(result) => result.map_err(|e| e.to_string()),
+      Err(_) => Err("dropped".to_string()),
+    }
+  }
+
+  #[test]
+  fn runs_by_priority_then_in_arrival_order() {
+    let (worker, _) = spawn(8);
+    let (release, _) = occupy(&worker);
+
+    let first_normal = record(&worker, "first normal", Priority::Normal).unwrap();
+    let background = record(&worker, "background", Priority::Background).unwrap();
+    let live = record(&worker, "live", Priority::Live).unwrap();
+    let second_normal = record(&worker, "second normal", Priority::Normal).unwrap();
+    release.send(()).unwrap();
+
+    assert_eq!(outcome(live).unwrap(), ["live"]);
+    assert_eq!(outcome(first_normal).unwrap(), ["live", "first normal"]);
+    assert_eq!(
+      outcome(second_normal).unwrap(),
+      ["live", "first normal", "second normal"]
+    );
+    assert_eq!(
+      outcome(background).unwrap(),
+      ["live", "first normal", "second normal", "background"]
+    );
+  }
+
+  #[test]
+  fn full_queue_drops_a_lower_priority_request_or_rejects() {
+    let (worker, _) = spawn(2);
+    let (release, _) = occupy(&worker);
+
+    let normal = record(&worker, "normal", Priority::Normal).unwrap();
+    let background = record(&worker, "background", Priority::Background).unwrap();
+    let live = record(&worker, "live", Priority::Live).unwrap();
+
+    let dropped = outcome(background).unwrap_err();
+    assert!(dropped.contains("full queue"), "{}", dropped);
+    assert!(record(&worker, "rejected", Priority::Normal).is_err());
+    assert!(record(&worker, "rejected", Priority::Background).is_err());
+
+    let status = worker.status();
+    assert_eq!(status.queued, 2);
+    assert!(status.running);
+    assert_eq!(status.capacity, 2);
+
+    release.send(()).unwrap();
+    assert_eq!(outcome(normal).unwrap(), ["live", "normal"]);
+    drop(live);
+  }
+
+  #[test]
+  fn cancels_queued_and_running_requests() {
+    let (worker, _) = spawn(8);
+
+    let (started, has_started) = mpsc::channel();
+    let running = worker
+      .submit(
+        request("running", Priority::Normal),
+        move |_, cancellation| {
+          started.send(()).unwrap();
+          while !cancellation.is_cancelled() {
+            thread::sleep(Duration::from_millis(1));
+          }
+          cancellation.check()
+        },
+      )
+      .unwrap();
+    has_started.recv().unwrap();
+    let queued = record(&worker, "queued", Priority::Normal).unwrap();
+    let kept = record(&worker, "kept", Priority::Normal).unwrap();
+
+    assert!(worker.cancel("queued"));
+    assert!(worker.cancel("running"));
+    assert!(!worker.cancel("unknown"));
+
+    assert!(outcome(queued).unwrap_err().contains("cancelled"));
+    assert!(
+      outcome(running)
+        .unwrap_err()
+        .contains("cancelled")
+    );
+    assert_eq!(outcome(kept).unwrap(), ["kept"]);
+  }
+
+  #[test]
+  fn reports_an_idle_worker() {
+    let (worker, _) = spawn(4);
+
+    let status = worker.status();
+    assert_eq!(status.queued, 0);
+    assert!(!status.running);
+    assert_eq!(status.capacity, 4);
+  }
+
+  #[test]
+  fn dropping_stops_the_thread_and_fails_pending_requests() {
+    let (worker, stopped) = spawn(8);
+    let (release, running) = occupy(&worker);
+    let queued = record(&worker, "queued", Priority::Normal).unwrap();
+
+    drop(worker);
+    assert!(outcome(queued).unwrap_err().contains("unloaded"));
+
+    release.send(()).unwrap();
+    assert!(
+      outcome(running)
+        .unwrap_err()
+        .contains("cancelled")
+    );
+    assert_eq!(
+      stopped.recv_timeout(Duration::from_secs(5)),
+      Err(mpsc::RecvTimeoutError::Disconnected)
+    );
+  }
+}
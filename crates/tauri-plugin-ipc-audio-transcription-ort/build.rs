@@ -1,4 +1,21 @@
-const COMMANDS: &[&str] = &["load_ort_model_whisper", "ipc_audio_transcription"];
+const COMMANDS: &[&str] = &[
+  "load_ort_model_whisper",
+  "unload_ort_model_whisper",
+  "get_loaded_whisper_model",
+  "ipc_audio_transcription",
+  "ipc_audio_transcription_with_timestamps",
+  "ipc_audio_transcription_batch",
+  "transcribe_file",
+  "detect_language",
+  "start_transcription_stream",
+  "push_transcription_stream",
+  "stop_transcription_stream",
+  "cancel_transcription",
+  "get_transcription_queue_status",
+  "set_transcription_session_options",
+  "get_transcription_session_options",
+  "list_execution_providers",
+];
 
 fn main() {
   tauri_plugin::Builder::new(COMMANDS).build();
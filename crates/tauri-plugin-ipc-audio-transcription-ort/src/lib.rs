@@ -1,57 +1,272 @@
-use std::sync::Mutex;
+use std::{
+  collections::HashMap,
+  path::PathBuf,
+  sync::{
+    Arc,
+    Mutex,
+    PoisonError,
+    atomic::{AtomicBool, Ordering},
+  },
+};
 
-use clap::ValueEnum;
-use log::info;
+use anyhow::anyhow;
+use log::{error, info, warn};
+use serde::Serialize;
+#[cfg(debug_assertions)]
+use specta_typescript::{BigIntExportBehavior, Typescript};
 use tauri::{
+  Emitter,
   Manager,
   Runtime,
+  ipc::Channel,
   plugin::{Builder as PluginBuilder, TauriPlugin},
 };
 
-mod helpers;
-mod models;
-
 #[derive(Default)]
 struct AppDataWhisperProcessor {
-  whisper_processor: Option<models::whisper::whisper::WhisperPipeline>,
+  /// Runs the loaded pipeline, requests queue up there instead of holding this lock.
+  worker:             Option<InferenceWorker<WhisperPipeline>>,
+  /// The model type `worker` was loaded as, where it was loaded from, its precision and the
+  /// session options it was loaded with.
+  loaded_model:       Option<(String, ModelSource, ModelPrecision, SessionOptions)>,
+  /// Session options of the models loaded from now on.
+  session_options:    SessionOptions,
+  streaming_sessions: HashMap<u32, TranscriptionStream>,
+  next_session_id:    u32,
+}
+
+/// A streaming session and what its pushes need to know about it.
+#[derive(Clone)]
+struct TranscriptionStream {
+  session: Arc<Mutex<StreamingSession>>,
+  /// Set once the stream is stopped, the pushes still queued or running then do nothing.
+  closed:  Arc<AtomicBool>,
+}
+
+impl TranscriptionStream {
+  fn close(&self) {
+    self.closed.store(true, Ordering::Relaxed);
+  }
+
+  fn is_closed(&self) -> bool {
+    self.closed.load(Ordering::Relaxed)
+  }
+}
+
+/// Request id of the pushes of a stream, so that stopping the stream cancels them.
+fn stream_request_id(session_id: u32) -> String {
+  format!("transcription-stream-{}", session_id)
+}
+
+#[derive(Serialize, specta::Type, Clone, Debug)]
+struct LoadedWhisperModel {
+  model_type: String,
+  source:     String,
+  precision:  ModelPrecision,
 }
 
-use crate::models::{
-  new_whisper_processor,
-  whisper::{self, whisper::WhichModel},
+/// Most transcription requests waiting for the model at once.
+const QUEUE_CAPACITY: usize = 16;
+
+const LOAD_MODEL_PROGRESS_EVENT: &str =
+  "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress";
+const TRANSCRIPTION_PARTIAL_EVENT: &str =
+  "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:transcription-partial";
+const TRANSCRIPTION_FINAL_EVENT: &str =
+  "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:transcription-final";
+
+use airi_audio_transcription_ort::{
+  helpers::audio::{convert_to_mono, load_audio_file},
+  models::{
+    new_whisper_processor,
+    whisper::{
+      self,
+      precision::ModelPrecision,
+      streaming::{StreamingFinal, StreamingPartial, StreamingSession},
+      whisper::{
+        LanguageProbability,
+        StreamedToken,
+        TokenListener,
+        TranscriptionOptions,
+        TranscriptionResult,
+        WhisperPipeline,
+      },
+      whisper_processor::SAMPLE_RATE,
+    },
+    whisper_model_source,
+  },
 };
+use airi_ort_common::{
+  huggingface::ProgressEmitter,
+  model_source::ModelSource,
+  optimized_model_cache::OptimizedModelCache,
+  session_options::{Provider, SessionOptions, available_providers},
+  worker::{InferenceWorker, PendingRequest, Priority, RequestOptions, WorkerStatus},
+};
+
+/// Forwards the download progress of the models to the window that loads them.
+struct WindowProgressEmitter<R: Runtime>(tauri::WebviewWindow<R>);
+
+impl<R: Runtime> ProgressEmitter for WindowProgressEmitter<R> {
+  fn emit_progress(
+    &self,
+    filename: String,
+    progress: f32,
+    total_size: usize,
+    current_size: usize,
+  ) {
+    if let Err(err) = self.0.emit(
+      LOAD_MODEL_PROGRESS_EVENT,
+      (false, filename, progress, total_size, current_size),
+    ) {
+      error!("Failed to emit model-load-progress: {:?}", err);
+    }
+  }
+
+  fn emit_done(
+    &self,
+    filename: String,
+  ) {
+    if let Err(err) = self
+      .0
+      .emit(LOAD_MODEL_PROGRESS_EVENT, (true, filename, 100.0))
+    {
+      error!("Failed to emit model-load-done: {:?}", err);
+    }
+  }
+}
+
+/// The cache of optimized models in the app cache directory, disabled when the app has none.
+fn optimized_model_cache<R: Runtime>(app: &tauri::AppHandle<R>) -> OptimizedModelCache {
+  let dir = app
+    .path()
+    .app_cache_dir()
+    .map(|dir| dir.join("onnx-optimized"));
+  if let Err(e) = &dir {
+    warn!("Optimized models will not be cached: {}", e);
+  }
+
+  OptimizedModelCache::new(dir.ok())
+}
+
+/// Converts a chunk captured at any sample rate and channel count (interleaved) into the 16kHz
+/// mono audio Whisper expects. Without a format the chunk is assumed to be 16kHz mono already.
+fn prepare_chunk(
+  chunk: Vec<f32>,
+  sample_rate: Option<u32>,
+  channels: Option<u16>,
+) -> Result<Vec<f32>, String> {
+  let sample_rate = sample_rate.map_or(SAMPLE_RATE, |sample_rate| sample_rate as usize);
+  let channels = channels.map_or(1, usize::from);
+  if sample_rate == SAMPLE_RATE && channels == 1 {
+    return Ok(chunk);
+  }
+
+  convert_to_mono(&chunk, sample_rate, channels, SAMPLE_RATE).map_err(|e| e.to_string())
+}
+
+/// Sends every decoded token over `on_token`, so that the frontend can show the text while the
+/// transcription runs.
+fn token_listener(on_token: Option<Channel<StreamedToken>>) -> Option<TokenListener> {
+  on_token.map(|channel| -> TokenListener {
+    Box::new(move |token| {
+      if let Err(e) = channel.send(token) {
+        info!("Failed to stream token: {}", e);
+      }
+    })
+  })
+}
+
+/// Queues `job` on the worker of the loaded model. The tokens it decodes are streamed to
+/// `on_token`, and it stops between two decoder steps once the request is cancelled.
+fn submit_transcription<R: Runtime, T: Send + 'static>(
+  app: &tauri::AppHandle<R>,
+  request: Option<RequestOptions>,
+  on_token: Option<Channel<StreamedToken>>,
+  job: impl FnOnce(&mut WhisperPipeline) -> anyhow::Result<T> + Send + 'static,
+) -> Result<PendingRequest<T>, String> {
+  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+  let data = data.lock().unwrap();
+  let worker = data
+    .worker
+    .as_ref()
+    .ok_or_else(|| "Whisper model is not loaded".to_string())?;
+
+  worker.submit(
+    request.unwrap_or_default(),
+    move |pipeline, cancellation| {
+      pipeline.set_token_listener(token_listener(on_token));
+      pipeline.set_cancellation_token(Some(cancellation.clone()));
+      let result = job(pipeline);
+      pipeline.set_token_listener(None);
+      pipeline.set_cancellation_token(None);
+      result
+    },
+  )
+}
 
 #[tauri::command]
+#[specta::specta]
 async fn load_ort_model_whisper<R: Runtime>(
   app: tauri::AppHandle<R>,
   window: tauri::WebviewWindow<R>,
   model_type: Option<String>,
+  model_dir: Option<String>,
+  model_files: Option<HashMap<String, String>>,
+  precision: Option<ModelPrecision>,
 ) -> Result<(), String> {
   info!("Loading models...");
 
-  {
+  let model_type = model_type.unwrap_or_else(|| "medium".to_string());
+  let precision = precision.unwrap_or_default();
+  let source = match ModelSource::local(model_dir, model_files) {
+    Some(source) => source,
+    None => whisper_model_source(&model_type).map_err(|e| e.to_string())?,
+  };
+
+  let session_options = {
     let data = app.state::<Mutex<AppDataWhisperProcessor>>();
     let data = data.lock().unwrap();
-    if data.whisper_processor.is_some() {
+    let model = (
+      model_type.clone(),
+      source.clone(),
+      precision,
+      data.session_options.clone(),
+    );
+    if data.loaded_model.as_ref() == Some(&model) {
       info!("Whisper model already loaded, skipping...");
       return Ok(());
     }
-  }
+    model.3
+  };
 
-  // Load the traditional whisper models first
-  match new_whisper_processor(
-    window,
-    Some(WhichModel::from_str(
-      model_type
-        .unwrap_or_else(|| "medium".to_string())
-        .as_str(),
-      true,
-    )?),
-  ) {
-    Ok(p) => {
+  // The previous model keeps serving requests until the new one is ready, the requests still
+  // queued for it are dropped once it is replaced. Downloading the model and building its sessions
+  // blocks, so it runs off the async runtime.
+  let cache = optimized_model_cache(&app);
+  let worker = tauri::async_runtime::spawn_blocking({
+    let source = source.clone();
+    let session_options = session_options.clone();
+    move || {
+      new_whisper_processor(
+        Arc::new(WindowProgressEmitter(window)),
+        Some(source),
+        precision,
+        &session_options,
+        &cache,
+      )
+      .and_then(|p| InferenceWorker::spawn("whisper-inference", p, QUEUE_CAPACITY))
+    }
+  })
+  .await
+  .map_err(|e| e.to_string())?;
+
+  match worker {
+    Ok(worker) => {
       let data = app.state::<Mutex<AppDataWhisperProcessor>>();
       let mut data = data.lock().unwrap();
-      data.whisper_processor = Some(p);
+      data.worker = Some(worker);
+      data.loaded_model = Some((model_type, source, precision, session_options));
       info!("Whisper model loaded successfully");
     },
     Err(e) => {
@@ -66,49 +281,503 @@ async fn load_ort_model_whisper<R: Runtime>(
 }
 
 #[tauri::command]
+#[specta::specta]
+async fn unload_ort_model_whisper<R: Runtime>(app: tauri::AppHandle<R>) -> Result<(), String> {
+  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+  let mut data = data.lock().unwrap();
+  if data.worker.take().is_some() {
+    info!("Whisper model unloaded");
+  }
+  data.loaded_model = None;
+  // The streams cannot go on without the model.
+  for (_, stream) in data.streaming_sessions.drain() {
+    stream.close();
+  }
+
+  Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn get_loaded_whisper_model<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<Option<LoadedWhisperModel>, String> {
+  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+  let data = data.lock().unwrap();
+
+  Ok(
+    data
+      .loaded_model
+      .as_ref()
+      .map(|(model_type, source, precision, _)| LoadedWhisperModel {
+        model_type: model_type.clone(),
+        source:     source.to_string(),
+        precision:  *precision,
+      }),
+  )
+}
+
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
 async fn ipc_audio_transcription<R: Runtime>(
   app: tauri::AppHandle<R>,
   chunk: Vec<f32>,
   language: Option<String>,
+  options: Option<TranscriptionOptions>,
+  sample_rate: Option<u32>,
+  channels: Option<u16>,
+  on_token: Option<Channel<StreamedToken>>,
+  request: Option<RequestOptions>,
 ) -> Result<String, String> {
   info!("Processing audio transcription...");
 
+  let chunk = prepare_chunk(chunk, sample_rate, channels)?;
+
+  let mut config = whisper::whisper::GenerationConfig::default();
+  config.language = language;
+  if let Some(options) = options {
+    options.apply(&mut config);
+  }
+
+  let transcription = submit_transcription(&app, request, on_token, move |processor| {
+    processor.transcribe(chunk.as_slice(), &config)
+  })?
+  .wait()
+  .await?;
+
+  info!("Transcription completed: {}", transcription);
+
+  Ok(transcription)
+}
+
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+async fn ipc_audio_transcription_with_timestamps<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  chunk: Vec<f32>,
+  language: Option<String>,
+  word_timestamps: Option<bool>,
+  options: Option<TranscriptionOptions>,
+  sample_rate: Option<u32>,
+  channels: Option<u16>,
+  on_token: Option<Channel<StreamedToken>>,
+  request: Option<RequestOptions>,
+) -> Result<TranscriptionResult, String> {
+  info!("Processing audio transcription with timestamps...");
+
+  let chunk = prepare_chunk(chunk, sample_rate, channels)?;
+
+  let mut config = whisper::whisper::GenerationConfig {
+    language,
+    return_timestamps: true,
+    return_word_timestamps: word_timestamps.unwrap_or(false),
+    ..Default::default()
+  };
+  if let Some(options) = options {
+    options.apply(&mut config);
+  }
+
+  let transcription = submit_transcription(&app, request, on_token, move |processor| {
+    processor.transcribe_with_timestamps(chunk.as_slice(), &config)
+  })?
+  .wait()
+  .await?;
+
+  info!(
+    "Transcription completed with {} segments: {}",
+    transcription.segments.len(),
+    transcription.text
+  );
+
+  Ok(transcription)
+}
+
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+async fn ipc_audio_transcription_batch<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  chunks: Vec<Vec<f32>>,
+  language: Option<String>,
+  word_timestamps: Option<bool>,
+  options: Option<TranscriptionOptions>,
+  sample_rate: Option<u32>,
+  channels: Option<u16>,
+  on_token: Option<Channel<StreamedToken>>,
+  request: Option<RequestOptions>,
+) -> Result<Vec<TranscriptionResult>, String> {
+  info!("Processing a batch of {} audio chunks...", chunks.len());
+
+  let chunks = chunks
+    .into_iter()
+    .map(|chunk| prepare_chunk(chunk, sample_rate, channels))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let mut config = whisper::whisper::GenerationConfig {
+    language,
+    return_timestamps: true,
+    return_word_timestamps: word_timestamps.unwrap_or(false),
+    ..Default::default()
+  };
+  if let Some(options) = options {
+    options.apply(&mut config);
+  }
+
+  let transcriptions = submit_transcription(&app, request, on_token, move |processor| {
+    processor.transcribe_batch(&chunks, &config)
+  })?
+  .wait()
+  .await?;
+
+  info!(
+    "Batch transcription completed for {} chunks",
+    transcriptions.len()
+  );
+
+  Ok(transcriptions)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn transcribe_file<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  path: String,
+  language: Option<String>,
+  word_timestamps: Option<bool>,
+  options: Option<TranscriptionOptions>,
+  on_token: Option<Channel<StreamedToken>>,
+  request: Option<RequestOptions>,
+) -> Result<TranscriptionResult, String> {
+  info!("Transcribing audio file {}...", path);
+
+  let file = PathBuf::from(&path);
+  let audio = tauri::async_runtime::spawn_blocking(move || load_audio_file(&file, SAMPLE_RATE))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+  let duration = audio.len() as f32 / SAMPLE_RATE as f32;
+
+  let mut config = whisper::whisper::GenerationConfig {
+    language,
+    return_timestamps: true,
+    return_word_timestamps: word_timestamps.unwrap_or(false),
+    ..Default::default()
+  };
+  if let Some(options) = options {
+    options.apply(&mut config);
+  }
+
+  let transcription = submit_transcription(&app, request, on_token, move |processor| {
+    processor.transcribe_with_timestamps(audio.as_slice(), &config)
+  })?
+  .wait()
+  .await?;
+
+  info!(
+    "Transcribed {:.1}s of audio into {} segments",
+    duration,
+    transcription.segments.len()
+  );
+
+  Ok(transcription)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn detect_language<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  chunk: Vec<f32>,
+  request: Option<RequestOptions>,
+) -> Result<Vec<LanguageProbability>, String> {
+  info!("Detecting spoken language...");
+
+  let languages = submit_transcription(&app, request, None, move |processor| {
+    processor.detect_language(chunk.as_slice())
+  })?
+  .wait()
+  .await?;
+
+  if let Some(detected) = languages.first() {
+    info!(
+      "Detected language: {} ({:.2})",
+      detected.language, detected.probability
+    );
+  }
+
+  Ok(languages)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn start_transcription_stream<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  language: Option<String>,
+  word_timestamps: Option<bool>,
+  options: Option<TranscriptionOptions>,
+  sample_rate: Option<u32>,
+  channels: Option<u16>,
+) -> Result<u32, String> {
   let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+  let mut data = data.lock().unwrap();
+  if data.worker.is_none() {
+    return Err("Whisper model is not loaded".to_string());
+  }
 
-  // Check if processor exists first
-  {
-    let data = data.lock().unwrap();
-    if data.whisper_processor.is_none() {
-      return Err("Whisper model is not loaded".to_string());
+  let mut config = whisper::whisper::GenerationConfig {
+    language,
+    return_word_timestamps: word_timestamps.unwrap_or(false),
+    ..Default::default()
+  };
+  if let Some(options) = options {
+    options.apply(&mut config);
+  }
+
+  let session_id = data.next_session_id;
+  let mut session = StreamingSession::new(session_id, config);
+  session
+    .set_input_format(
+      sample_rate.map_or(SAMPLE_RATE, |sample_rate| sample_rate as usize),
+      channels.map_or(1, usize::from),
+    )
+    .map_err(|e| e.to_string())?;
+
+  data.next_session_id = data.next_session_id.wrapping_add(1);
+  data.streaming_sessions.insert(
+    session_id,
+    TranscriptionStream {
+      session: Arc::new(Mutex::new(session)),
+      closed:  Arc::new(AtomicBool::new(false)),
+    },
+  );
+
+  info!("Started transcription stream {}", session_id);
+  Ok(session_id)
+}
+
+fn streaming_session<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  session_id: u32,
+) -> Result<TranscriptionStream, String> {
+  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+  let data = data.lock().unwrap();
+  data
+    .streaming_sessions
+    .get(&session_id)
+    .cloned()
+    .ok_or_else(|| format!("Transcription stream {} does not exist", session_id))
+}
+
+/// Transcribes the next chunk of a stream. Pushes are cancelled by stopping their stream, only
+/// the priority of `request` is used.
+#[tauri::command]
+#[specta::specta]
+async fn push_transcription_stream<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  window: tauri::WebviewWindow<R>,
+  session_id: u32,
+  chunk: Vec<f32>,
+  request: Option<RequestOptions>,
+) -> Result<(), String> {
+  let stream = streaming_session(&app, session_id)?;
+
+  // Someone is speaking, streams run before the requests that did not ask for a priority.
+  let mut request = request.unwrap_or_default();
+  request.priority.get_or_insert(Priority::Live);
+  request.id = Some(stream_request_id(session_id));
+  let push_stream = stream.clone();
+  let update = submit_transcription(&app, Some(request), None, move |processor| {
+    if push_stream.is_closed() {
+      return Ok(None);
+    }
+    let mut session = push_stream
+      .session
+      .lock()
+      .map_err(|_| anyhow!("Transcription stream {} failed", session_id))?;
+    session
+      .push_audio(processor, chunk.as_slice())
+      .map(Some)
+  })?
+  .wait()
+  .await?;
+
+  // The stream was stopped in the meantime, its final text was sent already.
+  let Some(update) = update.filter(|_| !stream.is_closed()) else {
+    return Ok(());
+  };
+  if let Some(committed) = update.committed {
+    window
+      .emit(TRANSCRIPTION_FINAL_EVENT, committed)
+      .map_err(|e| e.to_string())?;
+  }
+  window
+    .emit(TRANSCRIPTION_PARTIAL_EVENT, update.partial)
+    .map_err(|e| e.to_string())?;
+
+  Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn stop_transcription_stream<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  window: tauri::WebviewWindow<R>,
+  session_id: u32,
+) -> Result<String, String> {
+  let stream = {
+    let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+    let mut data = data.lock().unwrap();
+    let stream = data
+      .streaming_sessions
+      .remove(&session_id)
+      .ok_or_else(|| format!("Transcription stream {} does not exist", session_id))?;
+
+    // Drops the queued pushes and stops the running one between two decoder steps.
+    stream.close();
+    if let Some(worker) = &data.worker {
+      worker.cancel(&stream_request_id(session_id));
     }
+    stream
+  };
+
+  // Waits for the running push to let go of the session, off the async runtime. A push that
+  // panicked leaves the words committed before it.
+  let (committed, text) = tauri::async_runtime::spawn_blocking(move || {
+    stream
+      .session
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .finish()
+  })
+  .await
+  .map_err(|e| e.to_string())?;
+  if let Some(committed) = committed {
+    window
+      .emit(TRANSCRIPTION_FINAL_EVENT, committed)
+      .map_err(|e| e.to_string())?;
   }
 
-  // Then mutable borrow
+  info!("Stopped transcription stream {}: {}", session_id, text);
+  Ok(text)
+}
+
+/// Sets the session options of the models loaded from now on, the loaded model keeps its own
+/// until it is loaded again.
+#[tauri::command]
+#[specta::specta]
+async fn set_transcription_session_options<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  options: SessionOptions,
+) -> Result<(), String> {
+  info!("Setting transcription session options: {:?}", options);
+
+  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
   let mut data = data.lock().unwrap();
-  let processor = data.whisper_processor.as_mut().unwrap();
+  data.session_options = options;
 
-  let mut config = whisper::whisper::GenerationConfig::default();
-  config.language = language;
+  Ok(())
+}
 
-  let transcription = processor
-    .transcribe(chunk.as_slice(), &config)
-    .map_err(|e| e.to_string())?;
+#[tauri::command]
+#[specta::specta]
+async fn get_transcription_session_options<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<SessionOptions, String> {
+  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+  let data = data.lock().unwrap();
 
-  info!("Transcription completed: {}", transcription);
+  Ok(data.session_options.clone())
+}
 
-  Ok(transcription)
+/// The execution providers ONNX Runtime can use on this machine.
+#[tauri::command]
+#[specta::specta]
+async fn list_execution_providers() -> Result<Vec<Provider>, String> {
+  Ok(available_providers())
+}
+
+/// Cancels the queued or running requests with the given id. Returns whether there was any.
+#[tauri::command]
+#[specta::specta]
+async fn cancel_transcription<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  request_id: String,
+) -> Result<bool, String> {
+  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+  let data = data.lock().unwrap();
+  let cancelled = data
+    .worker
+    .as_ref()
+    .is_some_and(|worker| worker.cancel(&request_id));
+  if cancelled {
+    info!("Cancelled transcription request {}", request_id);
+  }
+
+  Ok(cancelled)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn get_transcription_queue_status<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<Option<WorkerStatus>, String> {
+  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
+  let data = data.lock().unwrap();
+
+  Ok(data.worker.as_ref().map(InferenceWorker::status))
 }
 
+const PLUGIN_NAME: &str = "ipc-audio-transcription-ort";
+
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
-  PluginBuilder::new("ipc-audio-transcription-ort")
+  let builder = tauri_specta::Builder::<R>::new()
+    .plugin_name(PLUGIN_NAME)
+    .commands(tauri_specta::collect_commands![
+      load_ort_model_whisper::<tauri::Wry>,
+      unload_ort_model_whisper::<tauri::Wry>,
+      get_loaded_whisper_model::<tauri::Wry>,
+      ipc_audio_transcription::<tauri::Wry>,
+      ipc_audio_transcription_with_timestamps::<tauri::Wry>,
+      ipc_audio_transcription_batch::<tauri::Wry>,
+      transcribe_file::<tauri::Wry>,
+      detect_language::<tauri::Wry>,
+      start_transcription_stream::<tauri::Wry>,
+      push_transcription_stream::<tauri::Wry>,
+      stop_transcription_stream::<tauri::Wry>,
+      cancel_transcription::<tauri::Wry>,
+      get_transcription_queue_status::<tauri::Wry>,
+      set_transcription_session_options::<tauri::Wry>,
+      get_transcription_session_options::<tauri::Wry>,
+      list_execution_providers,
+    ])
+    // Payloads of the stream events.
+    .typ::<StreamingPartial>()
+    .typ::<StreamingFinal>();
+
+  #[cfg(debug_assertions)]
+  builder
+    .export(
+      Typescript::default()
+        .header("// @ts-nocheck\n")
+        .bigint(BigIntExportBehavior::Number),
+      "../src/bindings/tauri-plugins/ipc-audio-transcription-ort.ts",
+    )
+    .expect("Failed to export typescript bindings");
+
+  PluginBuilder::new(PLUGIN_NAME)
+    .invoke_handler(builder.invoke_handler())
     .setup(|app, _| {
       info!("Initializing audio transcription plugin...");
-      app.manage(Mutex::new(AppDataWhisperProcessor::default()));
+      app.manage(Mutex::new(AppDataWhisperProcessor {
+        // The decoder runs the independent attention layers at once.
+        session_options: SessionOptions {
+          parallel_execution: true,
+          ..Default::default()
+        },
+        ..Default::default()
+      }));
       Ok(())
     })
-    .invoke_handler(tauri::generate_handler![
-      load_ort_model_whisper,
-      ipc_audio_transcription,
-    ])
     .build()
 }
@@ -1,2 +0,0 @@
-pub mod whisper;
-pub mod whisper_processor;
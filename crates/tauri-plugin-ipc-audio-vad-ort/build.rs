@@ -1,4 +1,12 @@
-const COMMANDS: &[&str] = &["load_ort_model_silero_vad", "ipc_audio_vad"];
+const COMMANDS: &[&str] = &[
+  "load_ort_model_silero_vad",
+  "ipc_audio_vad",
+  "cancel_vad",
+  "get_vad_queue_status",
+  "set_vad_session_options",
+  "get_vad_session_options",
+  "list_execution_providers",
+];
 
 fn main() {
   tauri_plugin::Builder::new(COMMANDS).build();
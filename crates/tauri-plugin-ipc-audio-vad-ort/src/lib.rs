@@ -1,46 +1,135 @@
-use std::sync::Mutex;
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
 
-use log::info;
+use airi_audio_vad_ort::models::{
+  new_silero_vad_processor,
+  silero_vad::{Processor, VADInferenceInput, VADInferenceResult},
+};
+use airi_ort_common::{
+  huggingface::ProgressEmitter,
+  model_source::ModelSource,
+  optimized_model_cache::OptimizedModelCache,
+  session_options::{Provider, SessionOptions, available_providers},
+  worker::{InferenceWorker, RequestOptions, WorkerStatus},
+};
+use log::{error, info, warn};
 use tauri::{
+  Emitter,
   Manager,
   Runtime,
   plugin::{Builder as PluginBuilder, TauriPlugin},
 };
 
-mod helpers;
-mod models;
+/// Event the download progress of the models is emitted as.
+const LOAD_MODEL_PROGRESS_EVENT: &str =
+  "tauri-plugins:tauri-plugin-ipc-audio-vad-ort:load-model-silero-vad-progress";
 
-use crate::models::{
-  new_silero_vad_processor,
-  silero_vad::{VADInferenceInput, VADInferenceResult},
-};
+/// Forwards the download progress of the models to the window that loads them.
+struct WindowProgressEmitter<R: Runtime>(tauri::WebviewWindow<R>);
+
+impl<R: Runtime> ProgressEmitter for WindowProgressEmitter<R> {
+  fn emit_progress(
+    &self,
+    filename: String,
+    progress: f32,
+    total_size: usize,
+    current_size: usize,
+  ) {
+    if let Err(err) = self.0.emit(
+      LOAD_MODEL_PROGRESS_EVENT,
+      (false, filename, progress, total_size, current_size),
+    ) {
+      error!("Failed to emit model-load-progress: {:?}", err);
+    }
+  }
+
+  fn emit_done(
+    &self,
+    filename: String,
+  ) {
+    if let Err(err) = self
+      .0
+      .emit(LOAD_MODEL_PROGRESS_EVENT, (true, filename, 100.0))
+    {
+      error!("Failed to emit model-load-done: {:?}", err);
+    }
+  }
+}
+
+/// The cache of optimized models in the app cache directory, disabled when the app has none.
+fn optimized_model_cache<R: Runtime>(app: &tauri::AppHandle<R>) -> OptimizedModelCache {
+  let dir = app
+    .path()
+    .app_cache_dir()
+    .map(|dir| dir.join("onnx-optimized"));
+  if let Err(e) = &dir {
+    warn!("Optimized models will not be cached: {}", e);
+  }
+
+  OptimizedModelCache::new(dir.ok())
+}
 
 #[derive(Default)]
 struct AppDataSileroVadProcessor {
-  silero_vad_processor: Option<crate::models::silero_vad::Processor>,
+  /// Runs the loaded model, requests queue up there instead of holding this lock.
+  worker:          Option<InferenceWorker<Processor>>,
+  /// Where `worker` was loaded from, `None` for the default repository, and the session options
+  /// it was loaded with.
+  loaded_model:    Option<(Option<ModelSource>, SessionOptions)>,
+  /// Session options of the models loaded from now on.
+  session_options: SessionOptions,
 }
 
+/// Most VAD requests waiting for the model at once, frames of live audio arrive often.
+const QUEUE_CAPACITY: usize = 64;
+
 #[tauri::command]
 async fn load_ort_model_silero_vad<R: Runtime>(
   app: tauri::AppHandle<R>,
   window: tauri::WebviewWindow<R>,
+  model_dir: Option<String>,
+  model_files: Option<HashMap<String, String>>,
 ) -> Result<(), String> {
   info!("Loading models...");
 
-  {
+  let source = ModelSource::local(model_dir, model_files);
+  let session_options = {
     let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
     let data = data.lock().unwrap();
-    if data.silero_vad_processor.is_some() {
+    let model = (source.clone(), data.session_options.clone());
+    if data.loaded_model.as_ref() == Some(&model) {
       info!("Silero VAD model already loaded, skipping...");
       return Ok(());
     }
-  }
+    model.1
+  };
+
+  // Downloading the model and building its session blocks, so it runs off the async runtime.
+  let cache = optimized_model_cache(&app);
+  let worker = tauri::async_runtime::spawn_blocking({
+    let source = source.clone();
+    let session_options = session_options.clone();
+    move || {
+      new_silero_vad_processor(
+        Arc::new(WindowProgressEmitter(window)),
+        source,
+        &session_options,
+        &cache,
+      )
+      .and_then(|p| InferenceWorker::spawn("silero-vad-inference", p, QUEUE_CAPACITY))
+    }
+  })
+  .await
+  .map_err(|e| e.to_string())?;
 
-  match new_silero_vad_processor(window) {
-    Ok(p) => {
+  match worker {
+    Ok(worker) => {
       let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
       let mut data = data.lock().unwrap();
-      data.silero_vad_processor = Some(p);
+      data.worker = Some(worker);
+      data.loaded_model = Some((source, session_options));
       info!("Silero VAD model loaded successfully");
     },
     Err(e) => {
@@ -58,29 +147,105 @@ async fn load_ort_model_silero_vad<R: Runtime>(
 async fn ipc_audio_vad<R: Runtime>(
   app: tauri::AppHandle<R>,
   input_data: VADInferenceInput,
+  request: Option<RequestOptions>,
 ) -> Result<VADInferenceResult, String> {
+  let pending = {
+    let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
+    let data = data.lock().unwrap();
+    let worker = data
+      .worker
+      .as_ref()
+      .ok_or_else(|| "Silero VAD model is not loaded".to_string())?;
+
+    worker.submit(request.unwrap_or_default(), move |processor, _| {
+      processor.inference(input_data)
+    })?
+  };
+
+  pending.wait().await
+}
+
+/// Sets the session options of the models loaded from now on, the loaded model keeps its own
+/// until it is loaded again.
+#[tauri::command]
+async fn set_vad_session_options<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  options: SessionOptions,
+) -> Result<(), String> {
+  info!("Setting VAD session options: {:?}", options);
+
+  let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
+  let mut data = data.lock().unwrap();
+  data.session_options = options;
+
+  Ok(())
+}
+
+#[tauri::command]
+async fn get_vad_session_options<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<SessionOptions, String> {
   let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
   let data = data.lock().unwrap();
 
-  if let Some(processor) = &data.silero_vad_processor {
-    processor
-      .inference(input_data)
-      .map_err(|e| e.to_string())
-  } else {
-    Err("Silero VAD model is not loaded".to_string())
-  }
+  Ok(data.session_options.clone())
+}
+
+/// The execution providers ONNX Runtime can use on this machine.
+#[tauri::command]
+async fn list_execution_providers() -> Result<Vec<Provider>, String> {
+  Ok(available_providers())
+}
+
+/// Cancels the queued or running requests with the given id. Returns whether there was any.
+#[tauri::command]
+async fn cancel_vad<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  request_id: String,
+) -> Result<bool, String> {
+  let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
+  let data = data.lock().unwrap();
+
+  Ok(
+    data
+      .worker
+      .as_ref()
+      .is_some_and(|worker| worker.cancel(&request_id)),
+  )
+}
+
+#[tauri::command]
+async fn get_vad_queue_status<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<Option<WorkerStatus>, String> {
+  let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
+  let data = data.lock().unwrap();
+
+  Ok(data.worker.as_ref().map(InferenceWorker::status))
 }
 
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
   PluginBuilder::new("ipc-audio-vad-ort")
     .setup(|app, _| {
       info!("Initializing audio VAD plugin...");
-      app.manage(Mutex::new(AppDataSileroVadProcessor::default()));
+      app.manage(Mutex::new(AppDataSileroVadProcessor {
+        // Frames are tiny, more threads only add synchronization.
+        session_options: SessionOptions {
+          intra_threads: Some(1),
+          ..Default::default()
+        },
+        ..Default::default()
+      }));
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
       load_ort_model_silero_vad,
-      ipc_audio_vad
+      ipc_audio_vad,
+      cancel_vad,
+      get_vad_queue_status,
+      set_vad_session_options,
+      get_vad_session_options,
+      list_execution_providers
     ])
     .build()
 }